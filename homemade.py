@@ -109,6 +109,8 @@ class MysticBot(ExampleEngine):
         self.chessBoard = chess.Board()
         self.timeout = 60  # Not used
         self.timeRemaining = 0
+        self.opponentTimeRemaining = None
+        self.increment = 0
         self.fenVals = []
         self.server_url = "http://localhost:8080"
 
@@ -121,7 +123,10 @@ class MysticBot(ExampleEngine):
             "current_fen": self.chessBoard.fen(),
             "history": self.fenVals,
             "time_left_ms": int(self.timeRemaining * 1000),
+            "increment_ms": int(self.increment * 1000),
         }
+        if self.opponentTimeRemaining is not None:
+            payload["opponent_time_left_ms"] = int(self.opponentTimeRemaining * 1000)
 
         print("Payload:")
         print("  Current FEN    :", payload["current_fen"])
@@ -148,7 +153,14 @@ class MysticBot(ExampleEngine):
         self.set_chess_board(board)
         self.fenVals.append(board.fen())
 
-        self.timeRemaining = time_limit.white_clock if board.turn == chess.WHITE else time_limit.black_clock
+        if board.turn == chess.WHITE:
+            self.timeRemaining = time_limit.white_clock
+            self.opponentTimeRemaining = time_limit.black_clock
+            self.increment = time_limit.white_inc or 0
+        else:
+            self.timeRemaining = time_limit.black_clock
+            self.opponentTimeRemaining = time_limit.white_clock
+            self.increment = time_limit.black_inc or 0
         if self.timeRemaining is None:
             self.timeRemaining = 60  # 60 seconds
 
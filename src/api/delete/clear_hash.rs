@@ -0,0 +1,46 @@
+use axum::{ extract::{ Query, State }, response::IntoResponse, Json, http::StatusCode };
+use serde::{ Deserialize, Serialize };
+use crate::bot::include::types::ServerState;
+
+#[derive(Debug, Deserialize)]
+pub struct ClearHashQuery {
+    pub game_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClearHashResponse {
+    pub message: String,
+}
+
+/// DELETE /game/hash — Empties a game's transposition table, keeping its size
+pub async fn clear_hash_handler(
+    State(state): State<ServerState>,
+    Query(params): Query<ClearHashQuery>
+) -> impl IntoResponse {
+    match state.engines.get(&params.game_id) {
+        // Other games rely on the shared table, it is not one game's to wipe
+        Some(engine) if engine.uses_shared_table() =>
+            (
+                StatusCode::CONFLICT,
+                Json(ClearHashResponse {
+                    message: format!("Game '{}' uses the shared hash", params.game_id),
+                }),
+            ),
+        Some(engine) => {
+            engine.transposition_table.clear();
+            (
+                StatusCode::OK,
+                Json(ClearHashResponse {
+                    message: format!("Hash of game '{}' cleared", params.game_id),
+                }),
+            )
+        }
+        None =>
+            (
+                StatusCode::NOT_FOUND,
+                Json(ClearHashResponse {
+                    message: format!("Game '{}' not found", params.game_id),
+                }),
+            ),
+    }
+}
@@ -1,5 +1,7 @@
 use axum::{ extract::{ Query, State }, response::IntoResponse, Json, http::StatusCode };
 use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use crate::api::post::ponder::abort_pondering;
 use crate::bot::include::types::{ ServerState };
 
 #[derive(Debug, Deserialize)]
@@ -18,6 +20,15 @@ pub async fn delete_game_handler(
     Query(params): Query<DeleteGameQuery>
 ) -> impl IntoResponse {
     if state.engines.remove(&params.game_id).is_some() {
+        // Stop and forget any background searches of this game
+        state.jobs.retain(|_, job| {
+            if job.game_id != params.game_id {
+                return true;
+            }
+            job.stop.store(true, Ordering::Relaxed);
+            false
+        });
+        abort_pondering(&state, &params.game_id);
         (
             StatusCode::OK,
             Json(DeleteGameResponse {
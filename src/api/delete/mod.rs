@@ -1 +1,2 @@
 pub mod delete_game;
+pub mod clear_hash;
@@ -6,28 +6,101 @@ use crate::bot::{
     algorithm::root::search,
     include::types::{
         EngineState,
+        PvLine,
         RepetitionHistory,
+        SearchLimits,
         ServerState,
-        TranspositionTable,
-        TT_TABLE_SIZE,
+        MAX_HASH_MB,
     },
+    util::{ board::fen_halfmove_clock, score::mate_in_moves },
 };
 
 #[derive(Debug, Deserialize)]
 pub struct EvalRequest {
     pub current_fen: String,
     pub history: Vec<String>,
-    time_left_ms: u128,
+    time_left_ms: Option<u128>,
     time_limit_ms: Option<u128>,
+    opponent_time_left_ms: Option<u128>,
+    increment_ms: Option<u128>,
+    moves_to_go: Option<u32>,
+    move_overhead_ms: Option<u128>,
+    depth: Option<u8>,
+    nodes: Option<u64>,
+    mate: Option<u8>,
+    skip_book: Option<bool>,
+    threads: Option<usize>,
+    multipv: Option<usize>,
+    search_moves: Option<Vec<String>>,
+    exclude_moves: Option<Vec<String>>,
+    null_move_pruning: Option<bool>,
+    contempt: Option<i32>,
+    hash_mb: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct BestMoveResponse {
     pub best_move: Option<String>,
     pub eval: i32,
+    /// Moves to mate when `eval` is a forced mate, positive when the side to move mates
+    pub mate: Option<i32>,
     pub nodes: u64,
     pub time: u128,
     pub depth: u8,
+    pub pv: Vec<String>,
+    pub lines: Vec<PvLineResponse>,
+    /// Permille of the transposition table the search filled
+    pub hashfull: u32,
+    /// Why the request was refused, if it was
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PvLineResponse {
+    pub r#move: String,
+    pub eval: i32,
+    pub mate: Option<i32>,
+    pub depth: u8,
+    pub pv: Vec<String>,
+}
+
+impl BestMoveResponse {
+    fn empty() -> Self {
+        Self {
+            best_move: None,
+            eval: 0,
+            mate: None,
+            nodes: 0,
+            time: 0,
+            depth: 0,
+            pv: Vec::new(),
+            lines: Vec::new(),
+            hashfull: 0,
+            error: None,
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            error: Some(message.into()),
+            ..Self::empty()
+        }
+    }
+}
+
+impl From<&PvLine> for PvLineResponse {
+    fn from(line: &PvLine) -> Self {
+        Self {
+            r#move: line.best_move.to_string(),
+            eval: line.eval,
+            mate: mate_in_moves(line.eval),
+            depth: line.depth,
+            pv: line.pv
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        }
+    }
 }
 
 pub async fn eval_position_handler(
@@ -37,16 +110,7 @@ pub async fn eval_position_handler(
     let current_board = match Board::from_str(&payload.current_fen) {
         Ok(b) => b,
         Err(_) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(BestMoveResponse {
-                    best_move: None,
-                    eval: 0,
-                    nodes: 0,
-                    time: 0,
-                    depth: 0,
-                }),
-            );
+            return (StatusCode::BAD_REQUEST, Json(BestMoveResponse::empty()));
         }
     };
 
@@ -58,33 +122,78 @@ pub async fn eval_position_handler(
         }
     }
 
-    let transposition_table = TranspositionTable::new(TT_TABLE_SIZE);
+    let hash_mb = payload.hash_mb.unwrap_or(state.global_map.hash_mb);
+    if !(1..=MAX_HASH_MB).contains(&hash_mb) {
+        return (StatusCode::BAD_REQUEST, Json(BestMoveResponse::empty()));
+    }
+    // The table only lives for this request, but still has to fit under the ceiling meanwhile
+    let Some(transposition_table) = state.global_map.reserve_table(hash_mb) else {
+        return (StatusCode::INSUFFICIENT_STORAGE, Json(BestMoveResponse::empty()));
+    };
 
-    let mut engine = EngineState {
-        game_id: "eval_temp".to_string(),
+    let mut engine = EngineState::new(
+        "eval_temp".to_string(),
         current_board,
         history,
-        statistics: Default::default(),
-        global_map: Arc::clone(&state.global_map),
-        transposition_table,
-    };
+        Arc::clone(&state.global_map),
+        transposition_table
+    );
+    engine.halfmove_clock = fen_halfmove_clock(&payload.current_fen);
+    if let Some(null_move_pruning) = payload.null_move_pruning {
+        engine.options.null_move_pruning = null_move_pruning;
+    }
+    if let Some(contempt) = payload.contempt {
+        engine.options.contempt = contempt;
+    }
 
-    let board = engine.current_board.clone();
-    let (best_move, nodes, time_taken_ms, eval, depth) = search(
-        payload.time_left_ms,
-        payload.time_limit_ms,
+    let board = engine.current_board;
+    let mut limits = SearchLimits {
+        time_left_ms: payload.time_left_ms,
+        time_limit_ms: payload.time_limit_ms,
+        opponent_time_ms: payload.opponent_time_left_ms,
+        increment_ms: payload.increment_ms.unwrap_or(0),
+        moves_to_go: payload.moves_to_go,
+        move_overhead_ms: payload.move_overhead_ms,
+        depth: payload.depth,
+        nodes: payload.nodes,
+        mate: payload.mate,
+        skip_book: payload.skip_book.unwrap_or(false),
+        threads: payload.threads,
+        multipv: payload.multipv,
+        ..Default::default()
+    };
+    let restricted = limits.restrict_root(
         &board,
-        &mut engine
+        payload.search_moves.as_deref(),
+        payload.exclude_moves.as_deref()
     );
+    if let Err(message) = restricted {
+        return (StatusCode::BAD_REQUEST, Json(BestMoveResponse::error(message)));
+    }
+    if !limits.is_bounded() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BestMoveResponse::error("Set a time, depth, nodes or mate limit")),
+        );
+    }
+    let result = search(&limits, &board, &mut engine, None);
 
     (
         StatusCode::OK,
         Json(BestMoveResponse {
-            best_move: best_move.map(|m| m.to_string()),
-            eval,
-            nodes,
-            time: time_taken_ms,
-            depth,
+            best_move: result.best_move.map(|m| m.to_string()),
+            eval: result.eval,
+            mate: mate_in_moves(result.eval),
+            nodes: result.nodes,
+            time: result.time_ms,
+            depth: result.depth,
+            pv: result.pv
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            lines: result.lines.iter().map(PvLineResponse::from).collect(),
+            hashfull: result.hashfull,
+            error: None,
         }),
     )
 }
@@ -0,0 +1,45 @@
+use axum::{ extract::State, http::StatusCode, response::IntoResponse, Json };
+use serde::Serialize;
+use crate::bot::include::types::ServerState;
+
+#[derive(Debug, Serialize)]
+pub struct GameMemoryResponse {
+    pub game_id: String,
+    /// Size of the table the game searches with
+    pub hash_mb: usize,
+    /// True when that table is the shared one, so its memory is not the game's alone
+    pub shared: bool,
+    pub hashfull: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryResponse {
+    pub limit_mb: usize,
+    pub used_mb: usize,
+    pub shared_hash_mb: Option<usize>,
+    pub games: Vec<GameMemoryResponse>,
+}
+
+/// GET /memory — Transposition table memory of the server and of every game
+pub async fn memory_handler(State(state): State<ServerState>) -> impl IntoResponse {
+    let mut games: Vec<GameMemoryResponse> = state.engines
+        .iter()
+        .map(|engine| GameMemoryResponse {
+            game_id: engine.game_id.clone(),
+            hash_mb: engine.transposition_table.size_mb(),
+            shared: engine.uses_shared_table(),
+            hashfull: engine.transposition_table.hashfull(),
+        })
+        .collect();
+    games.sort_by(|a, b| a.game_id.cmp(&b.game_id));
+
+    (
+        StatusCode::OK,
+        Json(MemoryResponse {
+            limit_mb: state.global_map.hash_memory_limit_mb,
+            used_mb: state.global_map.hash_memory_mb(),
+            shared_hash_mb: state.global_map.shared_table.as_ref().map(|table| table.size_mb()),
+            games,
+        }),
+    )
+}
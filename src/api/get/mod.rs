@@ -1,3 +1,5 @@
 pub mod root;
 pub mod get_eval;
 pub mod static_eval;
+pub mod search_job;
+pub mod memory;
@@ -0,0 +1,72 @@
+use axum::{ extract::{ Path, State }, http::StatusCode, response::IntoResponse, Json };
+use serde::Serialize;
+use crate::api::get::get_eval::PvLineResponse;
+use crate::bot::{ include::types::{ SearchJob, ServerState }, util::score::mate_in_moves };
+
+#[derive(Debug, Serialize)]
+pub struct SearchJobResponse {
+    pub job_id: String,
+    pub game_id: Option<String>,
+    pub finished: bool,
+    pub best_move: Option<String>,
+    pub eval: i32,
+    pub mate: Option<i32>,
+    pub nodes: u64,
+    pub time: u128,
+    pub depth: u8,
+    pub pv: Vec<String>,
+    pub lines: Vec<PvLineResponse>,
+    pub hashfull: u32,
+}
+
+impl SearchJobResponse {
+    pub fn from_job(job_id: &str, job: &SearchJob) -> Self {
+        let progress = job.progress.lock().unwrap();
+        let result = &progress.result;
+        Self {
+            job_id: job_id.to_string(),
+            game_id: Some(job.game_id.clone()),
+            finished: progress.finished_at.is_some(),
+            best_move: result.best_move.map(|m| m.to_string()),
+            eval: result.eval,
+            mate: mate_in_moves(result.eval),
+            nodes: result.nodes,
+            time: result.time_ms,
+            depth: result.depth,
+            pv: result.pv
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            lines: result.lines.iter().map(PvLineResponse::from).collect(),
+            hashfull: result.hashfull,
+        }
+    }
+
+    pub fn not_found(job_id: &str) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            game_id: None,
+            finished: false,
+            best_move: None,
+            eval: 0,
+            mate: None,
+            nodes: 0,
+            time: 0,
+            depth: 0,
+            pv: Vec::new(),
+            lines: Vec::new(),
+            hashfull: 0,
+        }
+    }
+}
+
+/// GET /game/search/:job_id — Live progress of a background search
+pub async fn search_progress_handler(
+    State(state): State<ServerState>,
+    Path(job_id): Path<String>
+) -> impl IntoResponse {
+    match state.jobs.get(&job_id) {
+        Some(job) => (StatusCode::OK, Json(SearchJobResponse::from_job(&job_id, &job))),
+        None => (StatusCode::NOT_FOUND, Json(SearchJobResponse::not_found(&job_id))),
+    }
+}
@@ -1,12 +1,15 @@
 use axum::{ extract::State, response::IntoResponse, Json, http::StatusCode };
 use serde::{ Deserialize, Serialize };
-use std::{ collections::HashMap, str::FromStr, sync::Arc };
+use std::{ str::FromStr, sync::Arc };
 use crate::bot::include::types::{
     EngineState,
     RepetitionHistory,
     ServerState,
-    TranspositionTable,
-    TT_TABLE_SIZE,
+    MAX_HASH_MB,
+};
+use crate::bot::{
+    algorithm::skill::{ skill_from_elo, MAX_SKILL_LEVEL },
+    util::board::fen_halfmove_clock,
 };
 use chess::Board;
 
@@ -15,6 +18,17 @@ pub struct NewGameRequest {
     pub game_id: String,
     pub current_fen: String,
     pub history: Vec<String>,
+    pub null_move_pruning: Option<bool>,
+    /// Overrides the server's contempt for this game
+    pub contempt: Option<i32>,
+    /// 0 to 20, 20 being full strength
+    pub skill_level: Option<u8>,
+    /// Picks the skill level closest to this rating, when `skill_level` is not set
+    pub target_elo: Option<u32>,
+    /// Transposition table size in MB, overrides the server's
+    pub hash_mb: Option<usize>,
+    /// Search with the server-wide table instead of a private one, `hash_mb` is then ignored
+    pub shared_hash: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -36,6 +50,43 @@ pub async fn new_game_handler(
         );
     }
 
+    let skill_level = match (payload.skill_level, payload.target_elo) {
+        (Some(skill), _) if skill > MAX_SKILL_LEVEL => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(NewGameResponse {
+                    message: format!("skill_level must be between 0 and {}", MAX_SKILL_LEVEL),
+                }),
+            );
+        }
+        (Some(skill), _) => skill,
+        (None, Some(elo)) => skill_from_elo(elo),
+        (None, None) => MAX_SKILL_LEVEL,
+    };
+
+    let shared_table = match (payload.shared_hash.unwrap_or(false), &state.global_map.shared_table) {
+        (true, Some(shared)) => Some(shared.clone()),
+        (true, None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(NewGameResponse {
+                    message: "This server has no shared hash".to_string(),
+                }),
+            );
+        }
+        (false, _) => None,
+    };
+
+    let hash_mb = payload.hash_mb.unwrap_or(state.global_map.hash_mb);
+    if shared_table.is_none() && !(1..=MAX_HASH_MB).contains(&hash_mb) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(NewGameResponse {
+                message: format!("hash_mb must be between 1 and {}", MAX_HASH_MB),
+            }),
+        );
+    }
+
     let current_board = match Board::from_str(&payload.current_fen) {
         Ok(b) => b,
         Err(_) => {
@@ -57,15 +108,38 @@ pub async fn new_game_handler(
         }
     }
 
-    let transposition_table = TranspositionTable::new(TT_TABLE_SIZE);
-    let engine = EngineState {
-        game_id: payload.game_id.clone(),
+    let transposition_table = match shared_table {
+        Some(shared) => shared,
+        None => {
+            let Some(table) = state.global_map.reserve_table(hash_mb) else {
+                return (
+                    StatusCode::INSUFFICIENT_STORAGE,
+                    Json(NewGameResponse {
+                        message: format!(
+                            "Only {} MB of hash memory left, use a smaller or the shared hash",
+                            state.global_map.free_hash_memory_mb()
+                        ),
+                    }),
+                );
+            };
+            table
+        }
+    };
+    let mut engine = EngineState::new(
+        payload.game_id.clone(),
         current_board,
         history,
-        statistics: HashMap::new(),
-        global_map: Arc::clone(&state.global_map),
-        transposition_table,
-    };
+        Arc::clone(&state.global_map),
+        transposition_table
+    );
+    engine.halfmove_clock = fen_halfmove_clock(&payload.current_fen);
+    if let Some(null_move_pruning) = payload.null_move_pruning {
+        engine.options.null_move_pruning = null_move_pruning;
+    }
+    if let Some(contempt) = payload.contempt {
+        engine.options.contempt = contempt;
+    }
+    engine.options.skill_level = Some(skill_level).filter(|&skill| skill < MAX_SKILL_LEVEL);
 
     state.engines.insert(payload.game_id.clone(), engine);
 
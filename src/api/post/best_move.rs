@@ -1,53 +1,132 @@
 use axum::{ extract::State, http::StatusCode, response::IntoResponse, Json };
 use serde::{ Deserialize, Serialize };
 use std::{ time::Instant };
-use crate::bot::{ algorithm::root::search, include::types::{ ServerState, Statistics } };
+use crate::api::{
+    get::get_eval::PvLineResponse,
+    post::ponder::{ finish_pondering, start_pondering },
+};
+use crate::bot::{
+    algorithm::root::search,
+    include::types::{ SearchLimits, ServerState, Statistics },
+    util::score::mate_in_moves,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct BestMoveQuery {
     game_id: String,
-    time_left_ms: u128,
+    time_left_ms: Option<u128>,
     time_limit_ms: Option<u128>,
+    opponent_time_left_ms: Option<u128>,
+    increment_ms: Option<u128>,
+    moves_to_go: Option<u32>,
+    move_overhead_ms: Option<u128>,
+    depth: Option<u8>,
+    nodes: Option<u64>,
+    mate: Option<u8>,
+    skip_book: Option<bool>,
+    threads: Option<usize>,
+    multipv: Option<usize>,
+    search_moves: Option<Vec<String>>,
+    exclude_moves: Option<Vec<String>>,
     update_state: Option<bool>,
+    /// Keep searching the expected reply in the background, needs `update_state`
+    ponder: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct BestMoveResponse {
     best_move: Option<String>,
     eval: i32,
+    mate: Option<i32>,
     nodes: u64,
     time: u128,
     depth: u8,
+    pv: Vec<String>,
+    lines: Vec<PvLineResponse>,
+    hashfull: u32,
+    /// Why the request was refused, if it was
+    error: Option<String>,
     new_position: String,
 }
 
+impl BestMoveResponse {
+    fn empty() -> Self {
+        Self {
+            best_move: None,
+            eval: 0,
+            mate: None,
+            nodes: 0,
+            time: 0,
+            depth: 0,
+            pv: Vec::new(),
+            lines: Vec::new(),
+            hashfull: 0,
+            error: None,
+            new_position: String::new(),
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            error: Some(message.into()),
+            ..Self::empty()
+        }
+    }
+}
+
 pub async fn best_move_handler(
     State(state): State<ServerState>,
     Json(params): Json<BestMoveQuery>
 ) -> impl IntoResponse {
-    let Some(mut engine) = state.engines.get_mut(&params.game_id) else {
+    let now = Instant::now();
+    let mut limits = SearchLimits {
+        time_left_ms: params.time_left_ms,
+        time_limit_ms: params.time_limit_ms,
+        opponent_time_ms: params.opponent_time_left_ms,
+        increment_ms: params.increment_ms.unwrap_or(0),
+        moves_to_go: params.moves_to_go,
+        move_overhead_ms: params.move_overhead_ms,
+        depth: params.depth,
+        nodes: params.nodes,
+        mate: params.mate,
+        skip_book: params.skip_book.unwrap_or(false),
+        threads: params.threads,
+        multipv: params.multipv,
+        ..Default::default()
+    };
+
+    let Some(current_board) = state.engines.get(&params.game_id).map(|e| e.current_board) else {
+        return (StatusCode::NOT_FOUND, Json(BestMoveResponse::empty()));
+    };
+    let restricted = limits.restrict_root(
+        &current_board,
+        params.search_moves.as_deref(),
+        params.exclude_moves.as_deref()
+    );
+    if let Err(message) = restricted {
+        return (StatusCode::BAD_REQUEST, Json(BestMoveResponse::error(message)));
+    }
+    if !limits.is_bounded() {
         return (
-            StatusCode::NOT_FOUND,
-            Json(BestMoveResponse {
-                best_move: None,
-                eval: 0,
-                nodes: 0,
-                time: 0,
-                depth: 0,
-                new_position: String::new(),
-            }),
+            StatusCode::BAD_REQUEST,
+            Json(BestMoveResponse::error("Set a time, depth, nodes or mate limit")),
         );
-    };
+    }
 
-    let now = Instant::now();
-    let board = engine.current_board.clone();
+    // Must happen before the game is locked, a ponder hit may still be thinking.
+    // A ponder search that cannot answer these limits is dropped without waiting for it.
+    let pondered = finish_pondering(&state, &params.game_id, &limits).await;
 
-    let (best_move, nodes, time, eval, depth) = search(
-        params.time_left_ms,
-        params.time_limit_ms,
-        &board,
-        &mut engine
-    );
+    let Some(mut engine) = state.engines.get_mut(&params.game_id) else {
+        return (StatusCode::NOT_FOUND, Json(BestMoveResponse::empty()));
+    };
+
+    let board = engine.current_board;
+    let result = match pondered {
+        Some(result) => result,
+        None => search(&limits, &board, &mut engine, None),
+    };
+    let best_move = result.best_move;
 
     let time_taken_ms = now.elapsed().as_millis();
 
@@ -57,14 +136,19 @@ pub async fn best_move_handler(
         // Update cumulative statistics under a fixed key (e.g., 0)
         let key = engine.current_board.get_hash();
         engine.statistics.entry(key).or_insert(Statistics {
-            nodes_explored: nodes,
-            time_taken_ms: time_taken_ms,
+            nodes_explored: result.nodes,
+            time_taken_ms,
         });
         if let Some(best) = best_move {
             // Update the current board with the selected move
-            let new_board = engine.current_board.make_move_new(best);
-            engine.current_board = new_board;
+            engine.play_move(best);
             new_position = engine.current_board.to_string();
+
+            if params.ponder.unwrap_or(false) {
+                if let Some(&expected_reply) = result.pv.get(1) {
+                    start_pondering(&state, &engine, expected_reply, params.threads);
+                }
+            }
         }
     }
 
@@ -72,11 +156,19 @@ pub async fn best_move_handler(
         StatusCode::OK,
         Json(BestMoveResponse {
             best_move: best_move.map(|m| m.to_string()),
-            eval,
-            nodes,
-            time,
-            depth,
+            eval: result.eval,
+            mate: mate_in_moves(result.eval),
+            nodes: result.nodes,
+            time: result.time_ms,
+            depth: result.depth,
+            pv: result.pv
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            lines: result.lines.iter().map(PvLineResponse::from).collect(),
+            hashfull: result.hashfull,
             new_position,
+            error: None,
         }),
     )
 }
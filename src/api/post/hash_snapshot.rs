@@ -0,0 +1,131 @@
+use axum::{ extract::State, http::StatusCode, response::IntoResponse, Json };
+use serde::{ Deserialize, Serialize };
+use crate::bot::{ include::types::ServerState, util::snapshot::snapshot_path };
+
+#[derive(Debug, Deserialize)]
+pub struct HashSnapshotRequest {
+    pub game_id: String,
+    /// File name inside the server's snapshot directory, without extension
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HashSnapshotResponse {
+    message: String,
+    entries: Option<usize>,
+}
+
+impl HashSnapshotResponse {
+    fn error(message: String) -> Json<Self> {
+        Json(Self { message, entries: None })
+    }
+}
+
+/// POST /game/hash/save — Writes a game's transposition table to a snapshot file
+pub async fn save_hash_handler(
+    State(state): State<ServerState>,
+    Json(payload): Json<HashSnapshotRequest>
+) -> impl IntoResponse {
+    let path = match snapshot_path(&state.global_map, &payload.name) {
+        Ok(path) => path,
+        Err(message) => {
+            return (StatusCode::BAD_REQUEST, HashSnapshotResponse::error(message));
+        }
+    };
+    let table = match state.engines.get(&payload.game_id) {
+        // Other games rely on the shared table, a snapshot would overwrite their entries too
+        Some(engine) if engine.uses_shared_table() => {
+            return (
+                StatusCode::CONFLICT,
+                HashSnapshotResponse::error(format!("Game '{}' uses the shared hash", payload.game_id)),
+            );
+        }
+        Some(engine) => engine.transposition_table.clone(),
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                HashSnapshotResponse::error(format!("Game '{}' not found", payload.game_id)),
+            );
+        }
+    };
+
+    // Searches may keep writing meanwhile, the snapshot is whatever each slot held when read
+    match tokio::task::spawn_blocking(move || table.save(&path)).await {
+        Ok(Ok(entries)) =>
+            (
+                StatusCode::OK,
+                Json(HashSnapshotResponse {
+                    message: format!("Hash of game '{}' saved as '{}'", payload.game_id, payload.name),
+                    entries: Some(entries),
+                }),
+            ),
+        Ok(Err(err)) =>
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                HashSnapshotResponse::error(format!("Could not save snapshot: {}", err)),
+            ),
+        Err(_) =>
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                HashSnapshotResponse::error("Snapshot task failed".to_string()),
+            ),
+    }
+}
+
+/// POST /game/hash/load — Merges a snapshot file into a game's own transposition table, never
+/// into the shared one
+pub async fn load_hash_handler(
+    State(state): State<ServerState>,
+    Json(payload): Json<HashSnapshotRequest>
+) -> impl IntoResponse {
+    let path = match snapshot_path(&state.global_map, &payload.name) {
+        Ok(path) => path,
+        Err(message) => {
+            return (StatusCode::BAD_REQUEST, HashSnapshotResponse::error(message));
+        }
+    };
+    if !path.is_file() {
+        return (
+            StatusCode::NOT_FOUND,
+            HashSnapshotResponse::error(format!("Snapshot '{}' not found", payload.name)),
+        );
+    }
+    let table = match state.engines.get(&payload.game_id) {
+        // Other games rely on the shared table, a snapshot would overwrite their entries too
+        Some(engine) if engine.uses_shared_table() => {
+            return (
+                StatusCode::CONFLICT,
+                HashSnapshotResponse::error(format!("Game '{}' uses the shared hash", payload.game_id)),
+            );
+        }
+        Some(engine) => engine.transposition_table.clone(),
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                HashSnapshotResponse::error(format!("Game '{}' not found", payload.game_id)),
+            );
+        }
+    };
+
+    match tokio::task::spawn_blocking(move || table.load(&path)).await {
+        Ok(Ok(entries)) =>
+            (
+                StatusCode::OK,
+                Json(HashSnapshotResponse {
+                    message: format!("Snapshot '{}' loaded into game '{}'", payload.name, payload.game_id),
+                    entries: Some(entries),
+                }),
+            ),
+        // Most likely a file that is not a snapshot, or one of an older format
+        Ok(Err(err)) =>
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                HashSnapshotResponse::error(format!("Could not load snapshot: {}", err)),
+            ),
+        Err(_) =>
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                HashSnapshotResponse::error("Snapshot task failed".to_string()),
+            ),
+    }
+}
@@ -1,4 +1,5 @@
 use axum::{ extract::State, Json, http::StatusCode, response::IntoResponse };
+use crate::api::post::ponder::ponder_move_played;
 use crate::bot::include::types::{ ServerState };
 use chess::{ ChessMove, MoveGen };
 use std::str::FromStr;
@@ -41,7 +42,7 @@ pub async fn make_move_handler(
         );
     };
 
-    let board = engine.current_board.clone();
+    let board = engine.current_board;
     let mut legal_moves = MoveGen::new_legal(&board);
     if !legal_moves.any(|m| m == chess_move) {
         return (
@@ -53,9 +54,8 @@ pub async fn make_move_handler(
         );
     }
 
-    engine.current_board = engine.current_board.make_move_new(chess_move);
-    let hash = engine.current_board.get_hash();
-    engine.history.increment(hash);
+    engine.play_move(chess_move);
+    ponder_move_played(&state, &payload.game_id, chess_move);
 
     let new_fen = engine.current_board.to_string();
 
@@ -1,3 +1,7 @@
 pub mod add_game;
 pub mod best_move;
 pub mod make_move;
+pub mod search_job;
+pub mod ponder;
+pub mod resize_hash;
+pub mod hash_snapshot;
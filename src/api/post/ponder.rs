@@ -0,0 +1,108 @@
+use chess::ChessMove;
+use std::{ sync::{ atomic::{ AtomicBool, Ordering }, Arc }, time::{ Duration, Instant } };
+use crate::api::post::search_job::{ spawn_search_job, wait_for_job };
+use crate::bot::{
+    include::types::{
+        EngineState,
+        PonderJob,
+        SearchLimits,
+        SearchResult,
+        ServerState,
+        TimeManager,
+    },
+};
+
+/// Starts searching the position after `expected_move`, the reply we predict from the PV
+pub fn start_pondering(
+    state: &ServerState,
+    engine: &EngineState,
+    expected_move: ChessMove,
+    threads: Option<usize>
+) {
+    abort_pondering(state, &engine.game_id);
+
+    let mut ponder_engine = engine.clone();
+    ponder_engine.play_move(expected_move);
+    ponder_engine.stop = Arc::new(AtomicBool::new(false));
+
+    let board = ponder_engine.current_board;
+    let limits = SearchLimits {
+        infinite: true,
+        threads,
+        // The expected reply is the opponent's move, the engine plays the side after it
+        engine_color: Some(engine.current_board.side_to_move()),
+        ..Default::default()
+    };
+    let job = spawn_search_job(Arc::clone(&state.engines), ponder_engine, limits);
+
+    state.ponders.insert(engine.game_id.clone(), PonderJob {
+        expected_move,
+        board,
+        job,
+        hit_at: None,
+    });
+}
+
+/// Called for every move applied to a game: keeps a correctly predicted ponder search alive
+pub fn ponder_move_played(state: &ServerState, game_id: &str, chess_move: ChessMove) {
+    let hit = match state.ponders.get_mut(game_id) {
+        Some(mut ponder) if ponder.hit_at.is_none() && ponder.expected_move == chess_move => {
+            ponder.hit_at = Some(Instant::now());
+            true
+        }
+        Some(_) => false,
+        None => {
+            return;
+        }
+    };
+
+    if !hit {
+        abort_pondering(state, game_id);
+    }
+}
+
+pub fn abort_pondering(state: &ServerState, game_id: &str) {
+    if let Some((_, ponder)) = state.ponders.remove(game_id) {
+        ponder.job.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Ends the game's ponder search. On a ponder hit it first keeps searching for whatever is
+/// left of this move's budget, counted from the moment the opponent's move came in, and
+/// then returns the result in place of a fresh search. Limits the ponder search could not
+/// have honoured stop it right away.
+pub async fn finish_pondering(
+    state: &ServerState,
+    game_id: &str,
+    limits: &SearchLimits
+) -> Option<SearchResult> {
+    if !limits.accepts_ponder_result() {
+        abort_pondering(state, game_id);
+        return None;
+    }
+    let (_, ponder) = state.ponders.remove(game_id)?;
+    let current_board = state.engines.get(game_id).map(|engine| engine.current_board);
+    let hit_at = ponder.hit_at.filter(|_| current_board == Some(ponder.board));
+
+    let time = TimeManager::new(limits, &ponder.board, &state.global_map);
+    let budget = time.soft_ms.or(time.hard_ms);
+    if let (Some(hit_at), Some(budget)) = (hit_at, budget) {
+        let remaining = Duration::from_millis(budget as u64).saturating_sub(hit_at.elapsed());
+        tokio::time::sleep(remaining).await;
+    }
+
+    ponder.job.stop.store(true, Ordering::Relaxed);
+    wait_for_job(&ponder.job).await;
+
+    hit_at?;
+    let result = ponder.job.progress.lock().unwrap().result.clone();
+    // A ponder search that never finished an iteration has nothing to offer
+    result.best_move?;
+    // Too shallow for the depth asked for, or just the book move a fresh search should skip
+    let deep_enough = limits.depth.is_none_or(|depth| result.depth >= depth);
+    let from_book = result.depth == 0;
+    if !deep_enough || (limits.skip_book && from_book) {
+        return None;
+    }
+    Some(result)
+}
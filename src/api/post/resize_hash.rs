@@ -0,0 +1,83 @@
+use axum::{ extract::State, http::StatusCode, response::IntoResponse, Json };
+use serde::{ Deserialize, Serialize };
+use crate::bot::include::types::{ ServerState, MAX_HASH_MB };
+
+#[derive(Debug, Deserialize)]
+pub struct ResizeHashRequest {
+    pub game_id: String,
+    pub hash_mb: usize,
+    /// Start from an empty table instead of carrying the current entries over
+    pub clear: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResizeHashResponse {
+    message: String,
+    /// Size actually allocated, rounded down to a power of two
+    hash_mb: Option<usize>,
+}
+
+/// POST /game/hash — Replaces a game's transposition table with one of a new size holding as
+/// many of its entries as fit, a game on the shared table gets a private one
+pub async fn resize_hash_handler(
+    State(state): State<ServerState>,
+    Json(payload): Json<ResizeHashRequest>
+) -> impl IntoResponse {
+    if !(1..=MAX_HASH_MB).contains(&payload.hash_mb) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ResizeHashResponse {
+                message: format!("hash_mb must be between 1 and {}", MAX_HASH_MB),
+                hash_mb: None,
+            }),
+        );
+    }
+
+    let current = state.engines.get(&payload.game_id).map(|e| e.transposition_table.clone());
+    let Some(current) = current else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ResizeHashResponse {
+                message: format!("Game '{}' not found", payload.game_id),
+                hash_mb: None,
+            }),
+        );
+    };
+    // Searches still running on the old table keep its memory, so the new one has to fit beside it
+    let Some(table) = state.global_map.reserve_table(payload.hash_mb) else {
+        return (
+            StatusCode::INSUFFICIENT_STORAGE,
+            Json(ResizeHashResponse {
+                message: format!(
+                    "Only {} MB of hash memory left for this game",
+                    state.global_map.free_hash_memory_mb()
+                ),
+                hash_mb: None,
+            }),
+        );
+    };
+    if !payload.clear.unwrap_or(false) {
+        table.copy_from(&current);
+    }
+
+    let Some(mut engine) = state.engines.get_mut(&payload.game_id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ResizeHashResponse {
+                message: format!("Game '{}' not found", payload.game_id),
+                hash_mb: None,
+            }),
+        );
+    };
+
+    // Searches already running keep the old table until they finish
+    engine.transposition_table = table;
+
+    (
+        StatusCode::OK,
+        Json(ResizeHashResponse {
+            message: format!("Hash of game '{}' resized", payload.game_id),
+            hash_mb: Some(engine.transposition_table.size_mb()),
+        }),
+    )
+}
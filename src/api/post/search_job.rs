@@ -0,0 +1,224 @@
+use axum::{ extract::{ Path, State }, http::StatusCode, response::IntoResponse, Json };
+use dashmap::DashMap;
+use serde::{ Deserialize, Serialize };
+use std::{ sync::{ atomic::{ AtomicBool, Ordering }, Arc, Mutex }, time::{ Duration, Instant } };
+use crate::api::get::search_job::SearchJobResponse;
+use crate::bot::{
+    algorithm::root::search,
+    include::types::{
+        EngineState,
+        SearchJob,
+        SearchLimits,
+        SearchProgress,
+        SearchResult,
+        ServerState,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct StartSearchRequest {
+    game_id: String,
+    time_left_ms: Option<u128>,
+    time_limit_ms: Option<u128>,
+    opponent_time_left_ms: Option<u128>,
+    increment_ms: Option<u128>,
+    moves_to_go: Option<u32>,
+    move_overhead_ms: Option<u128>,
+    depth: Option<u8>,
+    nodes: Option<u64>,
+    mate: Option<u8>,
+    /// Search until stopped through the stop endpoint
+    infinite: Option<bool>,
+    skip_book: Option<bool>,
+    threads: Option<usize>,
+    multipv: Option<usize>,
+    search_moves: Option<Vec<String>>,
+    exclude_moves: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StartSearchResponse {
+    job_id: Option<String>,
+    message: String,
+}
+
+/// Runs `search` for `engine` on the blocking pool, stopped through `engine.stop`
+pub fn spawn_search_job(
+    engines: Arc<DashMap<String, EngineState>>,
+    mut engine: EngineState,
+    limits: SearchLimits
+) -> SearchJob {
+    let job = SearchJob {
+        game_id: engine.game_id.clone(),
+        stop: Arc::clone(&engine.stop),
+        progress: Arc::new(Mutex::new(SearchProgress::default())),
+    };
+
+    let progress = Arc::clone(&job.progress);
+    tokio::task::spawn_blocking(move || {
+        let board = engine.current_board;
+        let report = |result: &SearchResult| {
+            progress.lock().unwrap().result = result.clone();
+        };
+        let result = search(&limits, &board, &mut engine, Some(&report));
+
+        {
+            let mut progress = progress.lock().unwrap();
+            progress.result = result;
+            progress.finished_at = Some(Instant::now());
+        }
+
+        // Hand the learned move ordering back, unless the game moved on meanwhile
+        if let Some(mut game) = engines.get_mut(&engine.game_id) {
+            if game.current_board == board {
+                game.ordering = engine.ordering;
+            }
+        }
+    });
+
+    job
+}
+
+/// Waits until a stopped (or finishing) job has written its final result
+pub async fn wait_for_job(job: &SearchJob) {
+    // The engine checks the flag on every node, so this only waits for it to unwind
+    while job.progress.lock().unwrap().finished_at.is_none() {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+// Finished jobs can still be polled for this long before they are forgotten
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Forgets jobs that finished more than `FINISHED_JOB_TTL` ago
+fn evict_finished_jobs(state: &ServerState) {
+    state.jobs.retain(|_, job| {
+        job.progress
+            .lock()
+            .unwrap()
+            .finished_at.is_none_or(|finished_at| finished_at.elapsed() < FINISHED_JOB_TTL)
+    });
+}
+
+/// POST /game/search — Starts a background search on the game's current position
+pub async fn start_search_handler(
+    State(state): State<ServerState>,
+    Json(payload): Json<StartSearchRequest>
+) -> impl IntoResponse {
+    // Search on a copy so the game is not locked while the engine thinks,
+    // the transposition table is shared between both
+    let Some(mut engine) = state.engines.get(&payload.game_id).map(|e| e.clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(StartSearchResponse {
+                job_id: None,
+                message: format!("Game ID '{}' not found", payload.game_id),
+            }),
+        );
+    };
+
+    let job_id = format!("{}-{:016x}", payload.game_id, rand::random::<u64>());
+    engine.stop = Arc::new(AtomicBool::new(false));
+
+    let mut limits = SearchLimits {
+        time_left_ms: payload.time_left_ms,
+        time_limit_ms: payload.time_limit_ms,
+        opponent_time_ms: payload.opponent_time_left_ms,
+        increment_ms: payload.increment_ms.unwrap_or(0),
+        moves_to_go: payload.moves_to_go,
+        move_overhead_ms: payload.move_overhead_ms,
+        depth: payload.depth,
+        nodes: payload.nodes,
+        mate: payload.mate,
+        infinite: payload.infinite.unwrap_or(false),
+        skip_book: payload.skip_book.unwrap_or(false),
+        threads: payload.threads,
+        multipv: payload.multipv,
+        ..Default::default()
+    };
+    let restricted = limits.restrict_root(
+        &engine.current_board,
+        payload.search_moves.as_deref(),
+        payload.exclude_moves.as_deref()
+    );
+    if let Err(message) = restricted {
+        return (StatusCode::BAD_REQUEST, Json(StartSearchResponse { job_id: None, message }));
+    }
+    // An endless search has to be asked for, it only ends through the stop endpoint
+    if !limits.infinite && !limits.is_bounded() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(StartSearchResponse {
+                job_id: None,
+                message: "Set a time, depth, nodes or mate limit, or infinite".to_string(),
+            }),
+        );
+    }
+
+    evict_finished_jobs(&state);
+
+    let job = spawn_search_job(Arc::clone(&state.engines), engine, limits);
+    state.jobs.insert(job_id.clone(), job);
+
+    (
+        StatusCode::ACCEPTED,
+        Json(StartSearchResponse {
+            job_id: Some(job_id),
+            message: format!("Search started for game '{}'", payload.game_id),
+        }),
+    )
+}
+
+/// POST /game/search/:job_id/stop — Stops a search and returns its best move so far, the job
+/// is forgotten afterwards
+pub async fn stop_search_handler(
+    State(state): State<ServerState>,
+    Path(job_id): Path<String>
+) -> impl IntoResponse {
+    let Some((_, job)) = state.jobs.remove(&job_id) else {
+        return (StatusCode::NOT_FOUND, Json(SearchJobResponse::not_found(&job_id)));
+    };
+
+    job.stop.store(true, Ordering::Relaxed);
+    wait_for_job(&job).await;
+
+    (StatusCode::OK, Json(SearchJobResponse::from_job(&job_id, &job)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::include::types::GlobalMap;
+
+    fn job(finished_at: Option<Instant>) -> SearchJob {
+        SearchJob {
+            game_id: "test".to_string(),
+            stop: Arc::new(AtomicBool::new(false)),
+            progress: Arc::new(
+                Mutex::new(SearchProgress { finished_at, ..Default::default() })
+            ),
+        }
+    }
+
+    #[test]
+    fn only_jobs_finished_past_the_ttl_are_evicted() {
+        let state = ServerState {
+            engines: Arc::new(DashMap::new()),
+            jobs: Arc::new(DashMap::new()),
+            ponders: Arc::new(DashMap::new()),
+            global_map: Arc::new(GlobalMap::default()),
+        };
+        let long_ago = Instant::now()
+            .checked_sub(FINISHED_JOB_TTL + Duration::from_secs(1))
+            .expect("clock started less than the TTL ago");
+        state.jobs.insert("running".to_string(), job(None));
+        state.jobs.insert("recent".to_string(), job(Some(Instant::now())));
+        state.jobs.insert("stale".to_string(), job(Some(long_ago)));
+
+        evict_finished_jobs(&state);
+
+        let mut kept: Vec<String> = state.jobs.iter().map(|job| job.key().clone()).collect();
+        kept.sort();
+        assert_eq!(kept, ["recent", "running"]);
+    }
+}
@@ -1,37 +1,64 @@
-use chess::{ Board, ChessMove, Color, File, Piece, Rank, Square };
+use chess::{
+    get_adjacent_files,
+    get_bishop_moves,
+    get_file,
+    get_knight_moves,
+    get_pawn_attacks,
+    get_rank,
+    get_rook_moves,
+    BitBoard,
+    Board,
+    ChessMove,
+    Color,
+    File,
+    Piece,
+    Rank,
+    Square,
+    EMPTY,
+};
 use crate::bot::{
-    include::types::{ BoundType, EngineState, GlobalMap, TTEntry },
-    util::{ board::BoardExt, piece::piece_value },
+    include::types::{ BoundType, EngineState, GlobalMap, SearchControl, TTEntry, TaperedScore },
+    util::{
+        board::BoardExt,
+        piece::{ piece_value, piece_value_end },
+        score::{ score_to_tt, MATE_SCORE },
+    },
 };
 
+// Per safe square beyond what a piece typically has, (piece, typical count, bonus)
+const MOBILITY: [(Piece, i32, TaperedScore); 4] = [
+    (Piece::Knight, 4, TaperedScore::new(4, 4)),
+    (Piece::Bishop, 6, TaperedScore::new(5, 5)),
+    (Piece::Rook, 7, TaperedScore::new(2, 4)),
+    (Piece::Queen, 13, TaperedScore::new(1, 2)),
+];
+const ROOK_OPEN_FILE: TaperedScore = TaperedScore::new(20, 10);
+const ROOK_HALF_OPEN_FILE: TaperedScore = TaperedScore::new(10, 5);
+const ROOK_ON_SEVENTH: TaperedScore = TaperedScore::new(15, 25);
+const BISHOP_PAIR: TaperedScore = TaperedScore::new(30, 50);
+const KNIGHT_OUTPOST: TaperedScore = TaperedScore::new(20, 10);
+
 fn distance_between(a: Square, b: Square) -> u8 {
     let file_distance = ((a.get_file().to_index() as i8) - (b.get_file().to_index() as i8)).abs();
     let rank_distance = ((a.get_rank().to_index() as i8) - (b.get_rank().to_index() as i8)).abs();
     (file_distance + rank_distance) as u8
 }
 
-fn evaluate_king_proximity(board: &Board, is_endgame: bool) -> i32 {
-    if is_endgame {
-        let white_king_sq = (board.pieces(Piece::King) & board.color_combined(Color::White))
-            .into_iter()
-            .next()
-            .unwrap();
-        let black_king_sq = (board.pieces(Piece::King) & board.color_combined(Color::Black))
-            .into_iter()
-            .next()
-            .unwrap();
-
-        let proximity = distance_between(white_king_sq, black_king_sq) as i32;
-        // Closer kings in endgame is generally good
-        return 14 - proximity;
-    }
-    0
+fn evaluate_king_proximity(board: &Board) -> TaperedScore {
+    let white_king_sq = board.king_square(Color::White);
+    let black_king_sq = board.king_square(Color::Black);
+
+    let proximity = distance_between(white_king_sq, black_king_sq) as i32;
+    // Closer kings in endgame help the side that is ahead to drive the other one into a
+    // corner, it means nothing while pieces are around
+    let ahead = (board.material_score(Color::White) - board.material_score(Color::Black)).signum();
+    TaperedScore::new(0, (14 - proximity) * ahead)
 }
 
-fn evaluate_connected_pawns(board: &Board) -> i32 {
+fn evaluate_connected_pawns(board: &Board) -> TaperedScore {
     use chess::{ Color::*, Piece::Pawn };
 
-    let mut score = 0;
+    let mut score = TaperedScore::default();
 
     for &color in &[White, Black] {
         let pawns = board.pieces(Pawn) & board.color_combined(color);
@@ -42,13 +69,13 @@ fn evaluate_connected_pawns(board: &Board) -> i32 {
 
             let connected = [-1, 1].iter().any(|&df| {
                 let f = (file as isize) + df;
-                if f < 0 || f > 7 {
+                if !(0..=7).contains(&f) {
                     return false;
                 }
 
                 [-1, 0, 1].iter().any(|&dr| {
                     let r = (rank as isize) + dr;
-                    if r < 0 || r > 7 {
+                    if !(0..=7).contains(&r) {
                         return false;
                     }
 
@@ -61,7 +88,8 @@ fn evaluate_connected_pawns(board: &Board) -> i32 {
             });
 
             if connected {
-                score += if color == White { 5 } else { -5 };
+                let bonus = TaperedScore::new(5, 10);
+                score += if color == White { bonus } else { -bonus };
             }
         }
     }
@@ -69,11 +97,11 @@ fn evaluate_connected_pawns(board: &Board) -> i32 {
     score
 }
 
-pub fn evaluate_passed_pawns(board: &Board) -> i32 {
+pub fn evaluate_passed_pawns(board: &Board) -> TaperedScore {
     use Color::{ White, Black };
     use Piece::Pawn;
 
-    let mut score = 0;
+    let mut score = TaperedScore::default();
 
     for &color in &[White, Black] {
         let pawns = board.pieces(Pawn) & board.color_combined(color);
@@ -104,11 +132,13 @@ pub fn evaluate_passed_pawns(board: &Board) -> i32 {
             });
 
             if is_passed {
-                let bonus = match color {
-                    White => 5 + 5 * (rank_idx as i32),
-                    Black => -5 - 5 * ((7 - rank_idx) as i32),
+                // Passers decide endgames, with pieces around they are easier to stop
+                let advance = match color {
+                    White => rank_idx as i32,
+                    Black => (7 - rank_idx) as i32,
                 };
-                score += bonus;
+                let bonus = TaperedScore::new(5 + 5 * advance, 10 + 10 * advance);
+                score += if color == White { bonus } else { -bonus };
             }
         }
     }
@@ -116,66 +146,152 @@ pub fn evaluate_passed_pawns(board: &Board) -> i32 {
     score
 }
 
-pub fn evaluate_board(board: &Board) -> i32 {
-    use chess::{ Piece::*, Color::* };
+/// Every square a pawn of `color` attacks
+fn pawn_attacks(board: &Board, color: Color) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    pawns.fold(EMPTY, |attacks, sq| attacks | get_pawn_attacks(sq, color, !EMPTY))
+}
 
-    if board.halfmove_clock() >= 100 {
-        return 0;
-    }
+/// Ranks in front of `rank` as seen by `color`
+fn ranks_ahead(color: Color, rank: Rank) -> BitBoard {
+    let ahead = match color {
+        Color::White => (rank.to_index() + 1)..8,
+        Color::Black => 0..rank.to_index(),
+    };
+    ahead.fold(EMPTY, |mask, r| mask | get_rank(Rank::from_index(r)))
+}
 
-    let white_material = board.material_score(White);
-    let black_material = board.material_score(Black);
+/// Pieces score for the squares they reach that no enemy pawn covers
+fn evaluate_mobility(board: &Board) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    for color in [Color::White, Color::Black] {
+        let own = board.color_combined(color);
+        let occupied = *board.combined();
+        let safe = !*own & !pawn_attacks(board, !color);
+
+        for (piece, typical, bonus) in MOBILITY {
+            for sq in board.pieces(piece) & own {
+                let attacks = match piece {
+                    Piece::Knight => get_knight_moves(sq),
+                    Piece::Bishop => get_bishop_moves(sq, occupied),
+                    Piece::Rook => get_rook_moves(sq, occupied),
+                    _ => get_bishop_moves(sq, occupied) | get_rook_moves(sq, occupied),
+                };
+                let squares = ((attacks & safe).popcnt() as i32) - typical;
+                let term = TaperedScore::new(bonus.mg * squares, bonus.eg * squares);
+                score += if color == Color::White { term } else { -term };
+            }
+        }
+    }
 
-    let mut white_total = 0;
-    let mut black_total = 0;
-    let mut white_bishops = 0;
-    let mut black_bishops = 0;
-    let mut white_knights = 0;
-    let mut black_knights = 0;
+    score
+}
 
-    for sq in chess::ALL_SQUARES {
-        if let Some(piece) = board.piece_on(sq) {
-            let color = board.color_on(sq).unwrap();
-            match color {
-                White => {
-                    white_total += 1;
-                    if piece == Bishop {
-                        white_bishops += 1;
-                    } else if piece == Knight {
-                        white_knights += 1;
-                    }
-                }
-                Black => {
-                    black_total += 1;
-                    if piece == Bishop {
-                        black_bishops += 1;
-                    } else if piece == Knight {
-                        black_knights += 1;
-                    }
-                }
+/// Rooks want files without pawns in their way, and the rank the enemy pawns start on
+fn evaluate_rooks(board: &Board) -> TaperedScore {
+    let mut score = TaperedScore::default();
+    let pawns = *board.pieces(Piece::Pawn);
+
+    for color in [Color::White, Color::Black] {
+        let own = board.color_combined(color);
+        let seventh = match color {
+            Color::White => Rank::Seventh,
+            Color::Black => Rank::Second,
+        };
+
+        for sq in board.pieces(Piece::Rook) & own {
+            let file = get_file(sq.get_file());
+            let mut term = TaperedScore::default();
+            if file & pawns == EMPTY {
+                term += ROOK_OPEN_FILE;
+            } else if file & pawns & own == EMPTY {
+                term += ROOK_HALF_OPEN_FILE;
+            }
+            if sq.get_rank() == seventh {
+                term += ROOK_ON_SEVENTH;
             }
+            score += if color == Color::White { term } else { -term };
         }
     }
 
-    let is_minor_or_lone = |total: usize, bishops: usize, knights: usize| {
-        match total {
-            1 => true,
-            2 if bishops == 1 => true,
-            2 if knights == 1 => true,
-            3 if knights == 2 => true,
-            _ => false,
-        }
+    score
+}
+
+fn evaluate_bishop_pair(board: &Board) -> TaperedScore {
+    let has_pair = |color: Color| {
+        (board.pieces(Piece::Bishop) & board.color_combined(color)).popcnt() >= 2
     };
+    match (has_pair(Color::White), has_pair(Color::Black)) {
+        (true, false) => BISHOP_PAIR,
+        (false, true) => -BISHOP_PAIR,
+        _ => TaperedScore::default(),
+    }
+}
 
-    if
-        is_minor_or_lone(white_total, white_bishops, white_knights) &&
-        is_minor_or_lone(black_total, black_bishops, black_knights)
-    {
+/// Knights deep in enemy land, backed by a pawn and out of reach of every enemy pawn
+fn evaluate_knight_outposts(board: &Board) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    for color in [Color::White, Color::Black] {
+        let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+        let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+
+        for sq in board.pieces(Piece::Knight) & board.color_combined(color) {
+            let relative_rank = match color {
+                Color::White => sq.get_rank().to_index(),
+                Color::Black => 7 - sq.get_rank().to_index(),
+            };
+            if !(3..=5).contains(&relative_rank) {
+                continue;
+            }
+            // Own pawns defending `sq` sit where an enemy pawn on `sq` would capture
+            let defended = get_pawn_attacks(sq, !color, own_pawns) != EMPTY;
+            let front_span = get_adjacent_files(sq.get_file()) & ranks_ahead(color, sq.get_rank());
+            let attackable = front_span & enemy_pawns != EMPTY;
+            if defended && !attackable {
+                score += if color == Color::White { KNIGHT_OUTPOST } else { -KNIGHT_OUTPOST };
+            }
+        }
+    }
+
+    score
+}
+
+/// Only the king is left, maybe with a single minor piece or two knights
+fn is_minor_or_lone(board: &Board, color: Color) -> bool {
+    let own = board.color_combined(color);
+    let total = own.popcnt();
+    let bishops = (board.pieces(Piece::Bishop) & own).popcnt();
+    let knights = (board.pieces(Piece::Knight) & own).popcnt();
+    match total {
+        1 => true,
+        2 if bishops == 1 => true,
+        2 if knights == 1 => true,
+        3 if knights == 2 => true,
+        _ => false,
+    }
+}
+
+/// Neither side has enough material left to force mate
+pub fn is_insufficient_material(board: &Board) -> bool {
+    is_minor_or_lone(board, Color::White) && is_minor_or_lone(board, Color::Black)
+}
+
+pub fn evaluate_board(board: &Board) -> i32 {
+    use chess::{ Piece::*, Color::* };
+
+    if is_insufficient_material(board) {
         return 0;
     }
 
-    let mut score = 0;
-    let is_endgame = white_material + black_material < 1600;
+    let white_minor_or_lone = is_minor_or_lone(board, White);
+    let black_minor_or_lone = is_minor_or_lone(board, Black);
+
+    let mut score = TaperedScore::default();
+    let table = |start: &[[i32; 8]; 8], end: &[[i32; 8]; 8], row: usize, col: usize| {
+        TaperedScore::new(start[row][col], end[row][col])
+    };
 
     for sq in chess::ALL_SQUARES {
         if let Some(piece) = board.piece_on(sq) {
@@ -186,29 +302,26 @@ pub fn evaluate_board(board: &Board) -> i32 {
                 Black => (7 - rank, file),
             };
 
-            let base = piece_value(piece);
+            let base = TaperedScore::new(piece_value(piece), piece_value_end(piece));
             let positional = match piece {
-                Pawn => GlobalMap::PAWN_TABLE[row][col],
-                Knight => GlobalMap::KNIGHT_TABLE[row][col],
-                Bishop => GlobalMap::BISHOP_TABLE[row][col],
-                Rook => GlobalMap::ROOK_TABLE[row][col],
-                Queen => GlobalMap::QUEEN_TABLE[row][col],
-                King => {
-                    if is_endgame {
-                        GlobalMap::KING_TABLE_END[row][col]
-                    } else {
-                        GlobalMap::KING_TABLE_START[row][col]
-                    }
-                }
+                Pawn => table(&GlobalMap::PAWN_TABLE_START, &GlobalMap::PAWN_TABLE_END, row, col),
+                Knight =>
+                    table(&GlobalMap::KNIGHT_TABLE_START, &GlobalMap::KNIGHT_TABLE_END, row, col),
+                Bishop =>
+                    table(&GlobalMap::BISHOP_TABLE_START, &GlobalMap::BISHOP_TABLE_END, row, col),
+                Rook => table(&GlobalMap::ROOK_TABLE_START, &GlobalMap::ROOK_TABLE_END, row, col),
+                Queen =>
+                    table(&GlobalMap::QUEEN_TABLE_START, &GlobalMap::QUEEN_TABLE_END, row, col),
+                King => table(&GlobalMap::KING_TABLE_START, &GlobalMap::KING_TABLE_END, row, col),
             };
 
             if color == White {
-                if !is_minor_or_lone(white_total, white_bishops, white_knights) {
+                if !white_minor_or_lone {
                     score += base;
                 }
                 score += positional;
             } else {
-                if !is_minor_or_lone(black_total, black_bishops, black_knights) {
+                if !black_minor_or_lone {
                     score -= base;
                 }
                 score -= positional;
@@ -218,55 +331,228 @@ pub fn evaluate_board(board: &Board) -> i32 {
 
     score += evaluate_connected_pawns(board);
     score += evaluate_passed_pawns(board);
-    score += evaluate_king_proximity(board, is_endgame);
+    score += evaluate_king_proximity(board);
+    score += evaluate_mobility(board);
+    score += evaluate_rooks(board);
+    score += evaluate_bishop_pair(board);
+    score += evaluate_knight_outposts(board);
+
+    // Slides from the middlegame to the endgame score as pieces come off, so a single
+    // trade never makes the eval jump
+    score.taper(board.game_phase())
+}
 
-    score
+/// Draws that depend on how the position was reached, so the TT cannot know about them and
+/// they are checked before it is probed. The root is exempt, it always has to come up with a move.
+pub fn is_search_draw(
+    board: &Board,
+    current_depth: u8,
+    repetition_count: u32,
+    engine_state: &EngineState
+) -> bool {
+    if current_depth == 0 {
+        return false;
+    }
+    if
+        repetition_count >= 3 ||
+        engine_state.is_path_repetition() ||
+        is_insufficient_material(board)
+    {
+        return true;
+    }
+    // A mate delivered on the 100th ply still wins
+    engine_state.search_halfmove_clock() >= 100 &&
+        board.status() != chess::BoardStatus::Checkmate
 }
 
 pub fn is_terminal(
     board: &Board,
     board_hash: u64,
-    depth: u8,
     current_depth: u8,
     color: i32,
-    repetition_count: u32,
+    control: &SearchControl,
     engine_state: &mut EngineState
 ) -> Option<(Option<ChessMove>, i32)> {
     match board.status() {
         chess::BoardStatus::Checkmate => {
-            let base_score = 1_000_000 - (current_depth as i32);
+            let base_score = MATE_SCORE - (current_depth as i32);
             let mate_score = if board.side_to_move() == chess::Color::White {
                 -base_score
             } else {
                 base_score
             };
             let score = mate_score * color;
-            engine_state.transposition_table.put((board_hash, depth), TTEntry {
-                value: score,
-                depth,
+            // A mate holds at any depth, so it answers every probe
+            engine_state.transposition_table.put(board_hash, TTEntry {
+                value: score_to_tt(score, current_depth),
+                depth: u8::MAX,
                 flag: BoundType::Exact,
                 best_move: None,
             });
             Some((None, score))
         }
-        chess::BoardStatus::Stalemate => {
-            engine_state.transposition_table.put((board_hash, depth), TTEntry {
-                value: 0,
-                depth,
-                flag: BoundType::Exact,
-                best_move: None,
-            });
-            Some((None, 0))
+        // Draws are scored with the contempt of whoever searches, so they stay out of the TT
+        chess::BoardStatus::Stalemate => Some((None, control.draw_score(color))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use crate::bot::include::types::{ RepetitionHistory, TranspositionTable };
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen).unwrap()
+    }
+
+    // Engine searching from `board`, whose clock stands at `halfmove_clock`
+    fn engine(board: &Board, halfmove_clock: u32) -> EngineState {
+        let mut history = RepetitionHistory::new();
+        history.increment(board.get_hash());
+        let mut engine_state = EngineState::new(
+            "test".to_string(),
+            *board,
+            history,
+            Arc::new(GlobalMap::default()),
+            TranspositionTable::new(1)
+        );
+        engine_state.search_path = vec![(board.get_hash(), halfmove_clock)];
+        engine_state
+    }
+
+    #[test]
+    fn lone_minor_pieces_cannot_mate() {
+        assert!(is_insufficient_material(&board("4k3/8/8/8/8/8/8/4K3 w - - 0 1")));
+        assert!(is_insufficient_material(&board("4k3/8/8/8/8/8/8/3NK3 w - - 0 1")));
+        assert!(is_insufficient_material(&board("4k3/8/8/8/8/8/8/2NNK3 w - - 0 1")));
+        assert!(is_insufficient_material(&board("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1")));
+
+        assert!(!is_insufficient_material(&board("4k3/8/8/8/8/8/8/3RK3 w - - 0 1")));
+        assert!(!is_insufficient_material(&board("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")));
+        assert!(!is_insufficient_material(&board("4k3/8/8/8/8/8/8/2BBK3 w - - 0 1")));
+        assert!(!is_insufficient_material(&board("4k3/8/8/8/8/8/8/2BNK3 w - - 0 1")));
+    }
+
+    #[test]
+    fn repetitions_are_draws_below_the_root() {
+        let start = Board::default();
+        let mut engine_state = engine(&start, 0);
+        let mut position = start;
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            position = engine_state.enter_position(&position, ChessMove::from_str(uci).unwrap());
         }
-        _ if repetition_count >= 3 => {
-            engine_state.transposition_table.put((board_hash, depth), TTEntry {
-                value: 0,
-                depth,
-                flag: BoundType::Exact,
-                best_move: None,
-            });
-            Some((None, 0))
+        assert_eq!(position, start);
+        assert!(engine_state.is_path_repetition());
+        assert!(is_search_draw(&position, 4, 2, &engine_state));
+        assert!(!is_search_draw(&position, 0, 2, &engine_state));
+
+        // A third occurrence in the game is a draw even off the search path
+        let engine_state = engine(&start, 0);
+        assert!(is_search_draw(&start, 1, 3, &engine_state));
+        assert!(!is_search_draw(&start, 1, 2, &engine_state));
+        assert!(!is_search_draw(&start, 0, 3, &engine_state));
+    }
+
+    #[test]
+    fn fifty_move_rule_unless_mated() {
+        let quiet = board("4k3/8/8/8/8/8/8/3RK3 w - - 0 1");
+        assert!(is_search_draw(&quiet, 1, 1, &engine(&quiet, 100)));
+        assert!(!is_search_draw(&quiet, 1, 1, &engine(&quiet, 99)));
+
+        // A mate delivered on the 100th ply still wins
+        let mated = board("3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+        assert_eq!(mated.status(), chess::BoardStatus::Checkmate);
+        assert!(!is_search_draw(&mated, 1, 1, &engine(&mated, 100)));
+    }
+
+    #[test]
+    fn insufficient_material_is_a_draw_below_the_root() {
+        let bare = board("4k3/8/8/8/8/8/8/3NK3 w - - 0 1");
+        assert!(is_search_draw(&bare, 1, 1, &engine(&bare, 0)));
+        assert!(!is_search_draw(&bare, 0, 1, &engine(&bare, 0)));
+        assert_eq!(evaluate_board(&bare), 0);
+    }
+
+    // The same position with the colours swapped, castling and en passant dropped
+    fn mirror(fen: &str) -> Board {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |c: char| {
+            if c.is_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }
+        };
+        let placement = fields[0]
+            .split('/')
+            .rev()
+            .map(|rank| rank.chars().map(swap_case).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("/");
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        board(&format!("{} {} - - 0 1", placement, side))
+    }
+
+    #[test]
+    fn evaluation_is_colour_symmetric() {
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w - - 4 4",
+            "r3k2r/1pp2ppp/p1nb1n2/3p4/3P1B2/2N2N2/PP3PPP/R3K2R b - - 0 12",
+            "8/5pk1/6p1/3P4/2P5/1K6/8/8 w - - 0 40",
+            "6k1/2R5/8/8/3N4/8/5PPP/6K1 w - - 0 30",
+        ] {
+            let eval = evaluate_board(&board(fen));
+            assert_eq!(evaluate_board(&mirror(fen)), -eval, "{}", fen);
         }
-        _ => None,
+    }
+
+    #[test]
+    fn endgames_are_scored_with_the_endgame_terms() {
+        // A passed pawn counts for more once the pieces are gone
+        let middlegame = board("r2qk2r/ppp2ppp/8/3P4/8/8/PPP2PPP/R2QK2R w - - 0 1");
+        let endgame = board("4k3/ppp2ppp/8/3P4/8/8/PPP2PPP/4K3 w - - 0 1");
+        assert_eq!(middlegame.game_phase(), 16);
+        assert_eq!(endgame.game_phase(), 0);
+        assert!(evaluate_board(&endgame) > evaluate_board(&middlegame));
+    }
+
+    #[test]
+    fn central_knights_beat_knights_on_the_rim() {
+        let center = board("4k3/7p/8/8/3N4/8/7P/4K3 w - - 0 1");
+        let rim = board("4k3/7p/8/8/N7/8/7P/4K3 w - - 0 1");
+        assert!(evaluate_mobility(&center).mg > evaluate_mobility(&rim).mg);
+        assert!(evaluate_board(&center) > evaluate_board(&rim));
+
+        // Squares an enemy pawn covers are no use to the knight
+        let covered = board("4k3/7p/6p1/8/3N4/8/7P/4K3 w - - 0 1");
+        assert!(evaluate_mobility(&covered).mg < evaluate_mobility(&center).mg);
+    }
+
+    #[test]
+    fn rooks_like_open_files_and_the_seventh() {
+        let rooks = |fen: &str| evaluate_rooks(&board(fen));
+        assert_eq!(rooks("4k3/p7/8/8/8/8/P7/R3K3 w - - 0 1"), TaperedScore::default());
+        assert_eq!(rooks("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1"), ROOK_HALF_OPEN_FILE);
+        assert_eq!(rooks("4k3/p7/8/8/8/8/P7/3RK3 w - - 0 1"), ROOK_OPEN_FILE);
+        assert_eq!(rooks("4k3/p2R4/8/8/8/8/P7/4K3 w - - 0 1"), ROOK_OPEN_FILE + ROOK_ON_SEVENTH);
+        assert_eq!(rooks("4k3/p7/8/8/8/8/P2r4/4K3 w - - 0 1"), -(ROOK_OPEN_FILE + ROOK_ON_SEVENTH));
+    }
+
+    #[test]
+    fn only_an_unmatched_bishop_pair_counts() {
+        let pair = |fen: &str| evaluate_bishop_pair(&board(fen));
+        assert_eq!(pair("2b1k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"), BISHOP_PAIR);
+        assert_eq!(pair("2b1kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"), -BISHOP_PAIR);
+        assert_eq!(pair("2b1kb2/8/8/8/8/8/8/2B1KB2 w - - 0 1"), TaperedScore::default());
+    }
+
+    #[test]
+    fn outposts_need_a_defender_and_no_enemy_pawn_to_chase_them() {
+        let outposts = |fen: &str| evaluate_knight_outposts(&board(fen));
+        assert_eq!(outposts("4k3/p7/8/4N3/3P4/8/8/4K3 w - - 0 1"), KNIGHT_OUTPOST);
+        assert_eq!(outposts("4k3/p7/3p4/4n3/8/8/8/4K3 w - - 0 1"), -KNIGHT_OUTPOST);
+        // No defender, a pawn that can still kick it, or not far enough up the board
+        assert_eq!(outposts("4k3/p7/8/4N3/8/8/8/4K3 w - - 0 1"), TaperedScore::default());
+        assert_eq!(outposts("4k3/5p2/8/4N3/3P4/8/8/4K3 w - - 0 1"), TaperedScore::default());
+        assert_eq!(outposts("4k3/p7/8/8/8/4N3/3P4/4K3 w - - 0 1"), TaperedScore::default());
     }
 }
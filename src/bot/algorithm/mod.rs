@@ -1,4 +1,7 @@
 pub mod root;
 pub mod negamax;
 pub mod eval;
-// pub mod quiet;
+pub mod quiet;
+pub mod ordering;
+pub mod time;
+pub mod skill;
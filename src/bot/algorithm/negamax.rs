@@ -1,48 +1,104 @@
-use chess::{ Board, ChessMove };
-use std::time::Instant;
-use crate::bot::algorithm::eval::{ evaluate_board, is_terminal };
-// use crate::bot::algorithm::quiet::quiescence_search;
-use crate::bot::algorithm::root::get_prioritized_moves;
-use crate::bot::include::types::{ BoundType, EngineState, TTEntry };
+use chess::{ Board, ChessMove, Piece, EMPTY };
+use once_cell::sync::Lazy;
+use crate::bot::algorithm::eval::{ evaluate_board, is_search_draw, is_terminal };
+use crate::bot::algorithm::quiet::quiescence_search;
+use crate::bot::algorithm::ordering::{ is_quiet, order_moves };
+use crate::bot::include::types::{ BoundType, EngineState, SearchControl, TTEntry };
+use crate::bot::util::{
+    board::{ is_noisy, BoardExt },
+    piece::piece_value,
+    score::{ is_aborted, mated_in, score_from_tt, score_to_tt, ABORTED_SCORE },
+};
 
+// Null move is only tried with at least this much depth left
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+
+// Late move reductions start after this many moves have been searched in full
+const LMR_MIN_MOVE_INDEX: usize = 3;
+// ... and only with at least this much depth left
+const LMR_MIN_DEPTH: u8 = 3;
+
+/// Reduction in plies for the `index`-th move at `depth`, growing with the log of both
+static LMR_TABLE: Lazy<[[u8; 64]; 64]> = Lazy::new(|| {
+    let mut table = [[0; 64]; 64];
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (index, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.75 + ((depth as f64).ln() * (index as f64).ln()) / 2.25) as u8;
+        }
+    }
+    table
+});
+
+fn late_move_reduction(depth: u8, index: usize) -> u8 {
+    LMR_TABLE[(depth as usize).min(63)][index.min(63)]
+}
+
+/// Null move is unsound in zugzwang, which is mostly a pawn-only ending for the side to move
+fn has_non_pawn_material(board: &Board) -> bool {
+    let side = board.side_to_move();
+    let pawns = (board.pieces(Piece::Pawn) & board.color_combined(side)).popcnt() as i32;
+    board.material_score(side) > pawns * piece_value(Piece::Pawn)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn negamax(
     board: &Board,
     mut alpha: i32,
-    beta: i32,
+    mut beta: i32,
     nodes: &mut u64,
-    deadline: Instant,
+    control: &SearchControl,
     engine_state: &mut EngineState,
     depth: u8,
     current_depth: u8,
     max_depth_reached: &mut u8,
-    color: i32
+    color: i32,
+    pv: &mut Vec<ChessMove>,
+    prev_move: Option<ChessMove>
 ) -> (Option<ChessMove>, i32) {
-    if Instant::now() >= deadline {
-        return (None, 0);
+    pv.clear();
+    if control.should_stop(*nodes) {
+        return (None, ABORTED_SCORE);
     }
 
-    *nodes += 1;
+    control.count_node(nodes);
     *max_depth_reached = (*max_depth_reached).max(current_depth);
 
+    // Mate distance pruning: no line from here can beat a mate already found closer to the root
+    if current_depth > 0 {
+        alpha = alpha.max(mated_in(current_depth));
+        beta = beta.min(-mated_in(current_depth + 1));
+        if alpha >= beta {
+            return (None, alpha);
+        }
+    }
+
     let board_hash = board.get_hash();
     let repetition_count = engine_state.history.get(board_hash);
 
-    // Transposition Table Lookup
-    {
-        if let Some(entry) = engine_state.transposition_table.get(&(board_hash, depth)) {
-            match entry.flag {
-                BoundType::Exact => {
-                    return (entry.best_move, entry.value);
-                }
-                BoundType::LowerBound if entry.value >= beta => {
-                    return (entry.best_move, entry.value);
-                }
-                BoundType::UpperBound if entry.value <= alpha => {
-                    return (entry.best_move, entry.value);
-                }
-                _ => {}
-            }
+    // A repeated position may well be in the TT with a real score, the draw has to win
+    if is_search_draw(board, current_depth, repetition_count, engine_state) {
+        return (None, control.draw_score(color));
+    }
+
+    // Transposition Table Lookup, the root always searches so it can report a full line.
+    // So do PV nodes, a cutoff there would cut the line short after the stored move.
+    let pv_node = alpha + 1 < beta;
+    let mut tt_move = None;
+    if let Some(entry) = engine_state.transposition_table.get(board_hash) {
+        let value = score_from_tt(entry.value, current_depth);
+        // Shallower entries still order the moves, they only cut when searched deep enough
+        let cutoff =
+            entry.depth >= depth &&
+            (match entry.flag {
+                BoundType::Exact => true,
+                BoundType::LowerBound => value >= beta,
+                BoundType::UpperBound => value <= alpha,
+            });
+        if cutoff && current_depth > 0 && !pv_node {
+            pv.extend(entry.best_move);
+            return (entry.best_move, value);
         }
+        tt_move = entry.best_move;
     }
 
     // Check terminal state
@@ -50,10 +106,9 @@ pub fn negamax(
         let Some(result) = is_terminal(
             board,
             board_hash,
-            depth,
             current_depth,
             color,
-            repetition_count,
+            control,
             engine_state
         )
     {
@@ -62,76 +117,305 @@ pub fn negamax(
 
     // Perform quiet search if depth is 0
     if depth == 0 {
-        // let eval = quiescence_search(
-        //     board,
-        //     alpha,
-        //     beta,
-        //     nodes,
-        //     deadline,
-        //     engine_state,
-        //     current_depth,
-        //     max_depth_reached,
-        //     color
-        // );
-        let eval = evaluate_board(board);
-        return (None, eval * color);
+        let eval = quiescence_search(
+            board,
+            alpha,
+            beta,
+            nodes,
+            control,
+            engine_state,
+            current_depth,
+            max_depth_reached,
+            color
+        );
+        return (None, eval);
+    }
+
+    // Null move pruning: if passing still fails high, a real move will too.
+    // The child of a null move has no previous move, so two are never played in a row.
+    if
+        engine_state.options.null_move_pruning &&
+        prev_move.is_some() &&
+        current_depth > 0 &&
+        depth >= NULL_MOVE_MIN_DEPTH &&
+        beta - alpha == 1 &&
+        has_non_pawn_material(board) &&
+        evaluate_board(board) * color >= beta
+    {
+        // `null_move` refuses positions in check
+        if let Some(null_board) = board.null_move() {
+            let reduction = 2 + depth / 6;
+            // Nothing before a passed move counts as a repetition, so the clock starts over
+            engine_state.search_path.push((null_board.get_hash(), 0));
+            let (_, eval) = negamax(
+                &null_board,
+                -beta,
+                -beta + 1,
+                nodes,
+                control,
+                engine_state,
+                depth.saturating_sub(1 + reduction),
+                current_depth + 1,
+                max_depth_reached,
+                -color,
+                &mut Vec::new(),
+                None
+            );
+            engine_state.search_path.pop();
+            if is_aborted(eval) {
+                return (None, ABORTED_SCORE);
+            }
+            if -eval >= beta {
+                return (None, beta);
+            }
+        }
     }
 
-    // Move generation
-    let prioritized_moves = get_prioritized_moves(board, false);
+    // Move generation, the root starts with the previous iteration's choice
+    if current_depth == 0 && control.root_best.is_some() {
+        tt_move = control.root_best;
+    }
+    let mut prioritized_moves = order_moves(
+        board,
+        &engine_state.ordering,
+        tt_move,
+        current_depth as usize,
+        prev_move
+    );
+    let restricted_root = current_depth == 0 && control.root_moves.is_some();
+    if let (0, Some(root_moves)) = (current_depth, &control.root_moves) {
+        prioritized_moves.retain(|(mv, _)| root_moves.contains(mv));
+    }
     if prioritized_moves.is_empty() {
         let eval = evaluate_board(board);
         return (None, eval * color);
     }
 
     // Negamax search
+    let in_check = *board.checkers() != EMPTY;
+    let original_alpha = alpha;
     let mut best_move = None;
     let mut best_eval = i32::MIN;
-    let mut flag = BoundType::UpperBound;
+    let mut child_pv = Vec::new();
+    let mut tried_quiets = Vec::new();
 
-    for (mv, _) in prioritized_moves {
-        let new_board = board.make_move_new(mv);
-        let new_hash = new_board.get_hash();
-        engine_state.history.increment(new_hash);
+    for (index, (mv, _)) in prioritized_moves.into_iter().enumerate() {
+        let new_board = engine_state.enter_position(board, mv);
 
-        let (_, eval) = negamax(
-            &new_board,
-            -beta,
-            -alpha,
-            nodes,
-            deadline,
-            engine_state,
-            depth - 1,
-            current_depth + 1,
-            max_depth_reached,
-            -color
-        );
+        let mut score = i32::MIN;
+        let mut full_depth_search = index > 0;
+
+        // LMR: quiet moves ordered late are searched shallower first, and again only if they beat alpha
+        if
+            index >= LMR_MIN_MOVE_INDEX &&
+            depth >= LMR_MIN_DEPTH &&
+            !in_check &&
+            !is_noisy(&board.classify_move(mv))
+        {
+            let reduction = late_move_reduction(depth, index).min(depth - 2);
+            if reduction > 0 {
+                let (_, eval) = negamax(
+                    &new_board,
+                    -alpha - 1,
+                    -alpha,
+                    nodes,
+                    control,
+                    engine_state,
+                    depth - 1 - reduction,
+                    current_depth + 1,
+                    max_depth_reached,
+                    -color,
+                    &mut child_pv,
+                    Some(mv)
+                );
+                score = -eval;
+                full_depth_search = score > alpha;
+            }
+        }
+
+        // PVS: the first move gets the full window, the rest only have to prove they are worse
+        if full_depth_search {
+            let (_, eval) = negamax(
+                &new_board,
+                -alpha - 1,
+                -alpha,
+                nodes,
+                control,
+                engine_state,
+                depth - 1,
+                current_depth + 1,
+                max_depth_reached,
+                -color,
+                &mut child_pv,
+                Some(mv)
+            );
+            score = -eval;
+        }
+
+        if index == 0 || (score > alpha && score < beta) {
+            let (_, eval) = negamax(
+                &new_board,
+                -beta,
+                -alpha,
+                nodes,
+                control,
+                engine_state,
+                depth - 1,
+                current_depth + 1,
+                max_depth_reached,
+                -color,
+                &mut child_pv,
+                Some(mv)
+            );
+            score = -eval;
+        }
 
-        let score = -eval;
+        engine_state.leave_position();
 
-        engine_state.history.decrement(new_hash);
+        // Stopped: nothing is stored, though the root still has the moves it finished
+        if is_aborted(score) {
+            if current_depth == 0 && best_move.is_some() {
+                return (best_move, best_eval);
+            }
+            pv.clear();
+            return (None, ABORTED_SCORE);
+        }
 
         if score > best_eval {
             best_eval = score;
             best_move = Some(mv);
+
+            // Triangular PV: this move followed by the child's principal line
+            pv.clear();
+            pv.push(mv);
+            pv.extend_from_slice(&child_pv);
         }
 
         alpha = alpha.max(score);
         if alpha >= beta {
-            flag = BoundType::LowerBound;
+            if is_quiet(board, mv) {
+                engine_state.ordering.record_cutoff(
+                    board,
+                    mv,
+                    &tried_quiets,
+                    depth,
+                    current_depth as usize,
+                    prev_move
+                );
+            }
             break;
-        } else {
-            flag = BoundType::Exact;
+        }
+        if is_quiet(board, mv) {
+            tried_quiets.push(mv);
         }
     }
 
-    // Store result in transposition table
-    engine_state.transposition_table.put((board_hash, depth), TTEntry {
-        value: best_eval,
-        depth,
-        flag,
-        best_move,
-    });
+    let flag = if best_eval >= beta {
+        BoundType::LowerBound
+    } else if best_eval > original_alpha {
+        BoundType::Exact
+    } else {
+        BoundType::UpperBound
+    };
+
+    // Store result in transposition table, a root restricted to some moves says nothing about the position
+    if !restricted_root {
+        engine_state.transposition_table.put(board_hash, TTEntry {
+            value: score_to_tt(best_eval, current_depth),
+            depth,
+            flag,
+            best_move,
+        });
+    }
 
     (best_move, best_eval)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use crate::bot::algorithm::root::search;
+    use crate::bot::include::types::{
+        GlobalMap,
+        RepetitionHistory,
+        SearchLimits,
+        SearchResult,
+        TranspositionTable,
+    };
+    use crate::bot::util::score::mate_in_moves;
+
+    fn search_fen(fen: &str, depth: u8, null_move_pruning: bool) -> SearchResult {
+        let board = Board::from_str(fen).unwrap();
+        let mut history = RepetitionHistory::new();
+        history.increment(board.get_hash());
+        let mut engine_state = EngineState::new(
+            "test".to_string(),
+            board,
+            history,
+            Arc::new(GlobalMap::default()),
+            TranspositionTable::new(1)
+        );
+        engine_state.options.null_move_pruning = null_move_pruning;
+        let limits = SearchLimits { depth: Some(depth), skip_book: true, ..Default::default() };
+        search(&limits, &board, &mut engine_state, None)
+    }
+
+    #[test]
+    fn null_move_needs_pieces_of_the_side_to_move() {
+        let has_pieces = |fen: &str| has_non_pawn_material(&Board::from_str(fen).unwrap());
+        assert!(has_pieces("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+        assert!(!has_pieces("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1"));
+        // Only the side to move counts, it is the one that would pass
+        assert!(has_pieces("4k3/pppp4/8/8/8/8/PPPP4/R3K3 w - - 0 1"));
+        assert!(!has_pieces("4k3/pppp4/8/8/8/8/PPPP4/R3K3 b - - 0 1"));
+    }
+
+    #[test]
+    fn pawn_endings_search_as_without_null_move() {
+        // Mutual zugzwang, whoever moves has to give way and passing would hide that
+        let fen = "8/8/3k4/3p4/3P4/3K4/8/8 w - - 0 1";
+        let with = search_fen(fen, 8, true);
+        let without = search_fen(fen, 8, false);
+        assert_eq!(with.best_move, without.best_move);
+        assert_eq!(with.eval, without.eval);
+        assert_eq!(with.nodes, without.nodes);
+    }
+
+    #[test]
+    fn null_move_keeps_the_mate() {
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let with = search_fen(fen, 5, true);
+        assert_eq!(mate_in_moves(with.eval), Some(2));
+        assert_eq!(with.eval, search_fen(fen, 5, false).eval);
+    }
+
+    #[test]
+    fn reductions_grow_with_depth_and_move_index() {
+        // The first move and the shallowest nodes are never reduced
+        for n in 0..64 {
+            assert_eq!(late_move_reduction(n, 0), 0);
+            assert_eq!(late_move_reduction(1, n as usize), 0);
+        }
+        for depth in 1..63 {
+            for index in 1..63 {
+                let reduction = late_move_reduction(depth, index);
+                assert!(reduction <= late_move_reduction(depth + 1, index));
+                assert!(reduction <= late_move_reduction(depth, index + 1));
+            }
+        }
+        // Past the end of the table the last entry holds
+        assert_eq!(late_move_reduction(200, 500), late_move_reduction(63, 63));
+        assert!(late_move_reduction(63, 63) > 0);
+    }
+
+    #[test]
+    fn reduced_quiet_move_that_mates_is_searched_again() {
+        // Both mating plans start with a quiet king move, which may well be reduced at first
+        let result = search_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1", 6, false);
+        assert_eq!(mate_in_moves(result.eval), Some(2));
+        let key = result.best_move.unwrap().to_string();
+        assert!(key == "c6b6" || key == "c6c7", "{} does not mate in 2", key);
+    }
+}
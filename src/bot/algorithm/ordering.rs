@@ -0,0 +1,210 @@
+use chess::{ Board, ChessMove, MoveGen, Piece };
+use crate::bot::include::types::{ MoveOrdering, MAX_PLY };
+use crate::bot::util::{ board::BoardExt, piece::piece_value };
+
+// Ordering bands, from first to last: TT move, winning captures and promotions,
+// killers, counter move, quiet moves by history, losing captures
+const TT_MOVE_SCORE: i32 = 10_000_000;
+const GOOD_CAPTURE_SCORE: i32 = 2_000_000;
+const FIRST_KILLER_SCORE: i32 = 1_000_000;
+const SECOND_KILLER_SCORE: i32 = 900_000;
+const COUNTER_MOVE_SCORE: i32 = 800_000;
+const BAD_CAPTURE_SCORE: i32 = -2_000_000;
+
+// History scores saturate here, so they always stay inside the quiet band
+const HISTORY_MAX: i32 = 16_384;
+
+fn butterfly_index(mv: ChessMove) -> usize {
+    mv.get_source().to_index() * 64 + mv.get_dest().to_index()
+}
+
+pub fn is_quiet(board: &Board, mv: ChessMove) -> bool {
+    board.piece_on(mv.get_dest()).is_none() &&
+        !board.is_en_passant(mv) &&
+        mv.get_promotion().is_none()
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        Self {
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![0; 2 * 64 * 64],
+            counter_moves: vec![None; 64 * 64],
+        }
+    }
+
+    /// Killers only make sense relative to the root, history is kept but fades
+    pub fn new_search(&mut self) {
+        self.killers.iter_mut().for_each(|k| {
+            *k = [None; 2];
+        });
+        self.history.iter_mut().for_each(|h| {
+            *h /= 2;
+        });
+    }
+
+    fn history_index(board: &Board, mv: ChessMove) -> usize {
+        board.side_to_move().to_index() * 64 * 64 + butterfly_index(mv)
+    }
+
+    pub fn history_score(&self, board: &Board, mv: ChessMove) -> i32 {
+        self.history[Self::history_index(board, mv)]
+    }
+
+    // Gravity update: large entries move less, which keeps them within +-HISTORY_MAX
+    fn update_history(&mut self, board: &Board, mv: ChessMove, bonus: i32) {
+        let entry = &mut self.history[Self::history_index(board, mv)];
+        let bonus = bonus.clamp(-HISTORY_MAX, HISTORY_MAX);
+        *entry += bonus - (*entry * bonus.abs()) / HISTORY_MAX;
+    }
+
+    /// Rewards a quiet move that failed high and penalises the quiet moves tried before it
+    pub fn record_cutoff(
+        &mut self,
+        board: &Board,
+        mv: ChessMove,
+        tried_quiets: &[ChessMove],
+        depth: u8,
+        ply: usize,
+        prev_move: Option<ChessMove>
+    ) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(mv) {
+                killers[1] = killers[0];
+                killers[0] = Some(mv);
+            }
+        }
+
+        let bonus = (depth as i32) * (depth as i32);
+        self.update_history(board, mv, bonus);
+        for &quiet in tried_quiets {
+            self.update_history(board, quiet, -bonus);
+        }
+
+        if let Some(prev) = prev_move {
+            self.counter_moves[butterfly_index(prev)] = Some(mv);
+        }
+    }
+}
+
+/// Legal moves sorted for the main search, best candidates first
+pub fn order_moves(
+    board: &Board,
+    ordering: &MoveOrdering,
+    tt_move: Option<ChessMove>,
+    ply: usize,
+    prev_move: Option<ChessMove>
+) -> Vec<(ChessMove, i32)> {
+    let killers = ordering.killers.get(ply).copied().unwrap_or([None; 2]);
+    let counter_move = prev_move.and_then(|prev| ordering.counter_moves[butterfly_index(prev)]);
+
+    let mut scored_moves: Vec<(ChessMove, i32)> = MoveGen::new_legal(board)
+        .map(|mv| {
+            let score = if Some(mv) == tt_move {
+                TT_MOVE_SCORE
+            } else if !is_quiet(board, mv) {
+                // MVV-LVA inside the band, SEE decides which band
+                let victim = if board.is_en_passant(mv) {
+                    Some(Piece::Pawn)
+                } else {
+                    board.piece_on(mv.get_dest())
+                };
+                let attacker = board.piece_on(mv.get_source()).map(piece_value).unwrap_or(0);
+                let mvv_lva =
+                    victim.map(piece_value).unwrap_or(0) * 10 -
+                    attacker / 10 +
+                    mv.get_promotion().map(piece_value).unwrap_or(0);
+                if board.static_exchange(mv) >= 0 {
+                    GOOD_CAPTURE_SCORE + mvv_lva
+                } else {
+                    BAD_CAPTURE_SCORE + mvv_lva
+                }
+            } else if Some(mv) == killers[0] {
+                FIRST_KILLER_SCORE
+            } else if Some(mv) == killers[1] {
+                SECOND_KILLER_SCORE
+            } else if Some(mv) == counter_move {
+                COUNTER_MOVE_SCORE
+            } else {
+                ordering.history_score(board, mv)
+            };
+            (mv, score)
+        })
+        .collect();
+
+    scored_moves.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored_moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn mv(uci: &str) -> ChessMove {
+        ChessMove::from_str(uci).unwrap()
+    }
+
+    #[test]
+    fn moves_come_in_band_order() {
+        // exd5 trades evenly, Qxd5 loses the queen to the e6 pawn
+        let board = Board::from_str("4k3/8/4p3/3p4/4P3/8/8/3QK3 w - - 0 1").unwrap();
+        let prev_move = Some(mv("e7e6"));
+        let mut ordering = MoveOrdering::new();
+        ordering.record_cutoff(&board, mv("d1d2"), &[], 4, 0, None);
+        ordering.record_cutoff(&board, mv("e1f2"), &[], 4, 0, None);
+        ordering.record_cutoff(&board, mv("d1c2"), &[], 2, 5, prev_move);
+
+        let ordered: Vec<ChessMove> = order_moves(&board, &ordering, Some(mv("d1a4")), 0, prev_move)
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .collect();
+
+        assert_eq!(ordered.len(), MoveGen::new_legal(&board).len());
+        assert_eq!(ordered[..5], [mv("d1a4"), mv("e4d5"), mv("e1f2"), mv("d1d2"), mv("d1c2")]);
+        assert_eq!(ordered.last(), Some(&mv("d1d5")));
+    }
+
+    #[test]
+    fn cutoffs_reward_the_move_and_punish_the_ones_before() {
+        let board = Board::default();
+        let mut ordering = MoveOrdering::new();
+        ordering.record_cutoff(&board, mv("g1f3"), &[mv("a2a3"), mv("h2h3")], 6, 2, None);
+
+        assert_eq!(ordering.killers[2], [Some(mv("g1f3")), None]);
+        assert_eq!(ordering.history_score(&board, mv("g1f3")), 36);
+        assert_eq!(ordering.history_score(&board, mv("a2a3")), -36);
+        // The same cutoff again does not push the first killer out
+        ordering.record_cutoff(&board, mv("g1f3"), &[], 6, 2, None);
+        assert_eq!(ordering.killers[2], [Some(mv("g1f3")), None]);
+
+        // Black's history is kept apart
+        let black = board.make_move_new(mv("e2e4"));
+        assert_eq!(ordering.history_score(&black, mv("g1f3")), 0);
+
+        ordering.new_search();
+        assert_eq!(ordering.killers[2], [None, None]);
+        assert_eq!(ordering.history_score(&board, mv("a2a3")), -18);
+    }
+
+    #[test]
+    fn history_saturates() {
+        let board = Board::default();
+        let mut ordering = MoveOrdering::new();
+        for _ in 0..1_000 {
+            ordering.record_cutoff(&board, mv("e2e4"), &[mv("d2d4")], u8::MAX, 0, None);
+        }
+        let best = ordering.history_score(&board, mv("e2e4"));
+        let worst = ordering.history_score(&board, mv("d2d4"));
+        assert!(best > 0 && best <= HISTORY_MAX);
+        assert!((-HISTORY_MAX..0).contains(&worst));
+        // Always below the killers, whatever the history says
+        assert!(best < SECOND_KILLER_SCORE && worst > BAD_CAPTURE_SCORE);
+    }
+}
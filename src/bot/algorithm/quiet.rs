@@ -1,80 +1,107 @@
 use chess::Board;
-use std::time::Instant;
-use crate::bot::algorithm::eval::{ evaluate_board, is_terminal };
+use crate::bot::algorithm::eval::{ evaluate_board, is_search_draw, is_terminal };
 use crate::bot::algorithm::root::get_prioritized_moves;
-use crate::bot::include::types::EngineState;
+use crate::bot::include::types::{ EngineState, SearchControl };
+use crate::bot::util::{
+    board::BoardExt,
+    piece::piece_value,
+    score::{ is_aborted, ABORTED_SCORE },
+};
 
+// Safety margin for delta pruning, roughly the best positional swing a capture can bring
+const DELTA_MARGIN: i32 = 200;
+
+#[allow(clippy::too_many_arguments)]
 pub fn quiescence_search(
     board: &Board,
     mut alpha: i32,
     beta: i32,
     nodes: &mut u64,
-    deadline: Instant,
+    control: &SearchControl,
     engine_state: &mut EngineState,
     current_depth: u8,
     max_depth_reached: &mut u8,
     color: i32
 ) -> i32 {
-    if Instant::now() >= deadline {
-        return 0;
+    // Stopped before counting the node, so a node budget is never overrun
+    if control.should_stop(*nodes) {
+        return ABORTED_SCORE;
     }
 
-    *nodes += 1;
+    control.count_node(nodes);
     *max_depth_reached = (*max_depth_reached).max(current_depth);
 
     let board_hash = board.get_hash();
     let repetition_count = engine_state.history.get(board_hash);
 
+    if is_search_draw(board, current_depth, repetition_count, engine_state) {
+        return control.draw_score(color);
+    }
+
     // ✅ Reuse is_terminal
     if
         let Some((_, score)) = is_terminal(
             board,
             board_hash,
-            0, // quiescence search is depth = 0
             current_depth,
             color,
-            repetition_count,
+            control,
             engine_state
         )
     {
         return score;
     }
 
+    let in_check = *board.checkers() != chess::EMPTY;
     let stand_pat = color * evaluate_board(board);
 
-    if stand_pat >= beta {
-        return stand_pat;
-    }
-    if stand_pat > alpha {
-        alpha = stand_pat;
+    // In check there is no standing pat, every evasion has to be looked at
+    let mut best_eval = if in_check { i32::MIN + 1 } else { stand_pat };
+    if !in_check {
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
     }
 
-    for (mv, _) in get_prioritized_moves(board, true) {
-        let new_board = board.make_move_new(mv);
-        let new_hash = new_board.get_hash();
-        engine_state.history.increment(new_hash);
+    for (mv, _) in get_prioritized_moves(board, !in_check) {
+        // Delta pruning: skip captures that cannot lift the score back to alpha
+        if !in_check && mv.get_promotion().is_none() {
+            let gain = board
+                .capture_pieces(mv)
+                .map(|(_, victim)| piece_value(victim))
+                .unwrap_or(0);
+            if stand_pat + gain + DELTA_MARGIN <= alpha {
+                continue;
+            }
+        }
+
+        let new_board = engine_state.enter_position(board, mv);
 
         let score = -quiescence_search(
             &new_board,
             -beta,
             -alpha,
             nodes,
-            deadline,
+            control,
             engine_state,
             current_depth + 1,
             max_depth_reached,
             -color
         );
 
-        engine_state.history.decrement(new_hash);
+        engine_state.leave_position();
 
+        if is_aborted(score) {
+            return ABORTED_SCORE;
+        }
+
+        best_eval = best_eval.max(score);
         if score >= beta {
             return score;
         }
-        if score > alpha {
-            alpha = score;
-        }
+        alpha = alpha.max(score);
     }
 
-    alpha
+    best_eval
 }
@@ -1,12 +1,21 @@
 use chess::{ Board, ChessMove, MoveGen };
-use std::time::{ Duration, Instant };
-use crate::bot::algorithm::negamax::negamax; // assumes renamed or placed negamax in the module
+use std::sync::{ atomic::{ AtomicBool, AtomicU64, Ordering }, Arc };
+use std::thread;
+use std::time::Instant;
+use crate::bot::algorithm::negamax::negamax;
 use crate::bot::algorithm::eval::evaluate_board;
+use crate::bot::algorithm::skill::{ pick_weaker_move, weakened_limits };
 use crate::bot::include::types::SpecialMove;
 use crate::bot::util::lookup::lookup_opening_db;
-use crate::bot::include::types::EngineState;
-use crate::bot::util::board::BoardExt;
-use crate::bot::util::piece::piece_value;
+use crate::bot::include::types::{
+    EngineState,
+    PvLine,
+    SearchControl,
+    SearchLimits,
+    SearchResult,
+    TimeManager,
+};
+use crate::bot::util::{ board::BoardExt, score::{ is_aborted, mate_in_moves } };
 
 pub fn get_prioritized_moves(board: &Board, only_noise: bool) -> Vec<(ChessMove, i32)> {
     let mut move_priority_pairs = Vec::new();
@@ -20,10 +29,9 @@ pub fn get_prioritized_moves(board: &Board, only_noise: bool) -> Vec<(ChessMove,
             {
                 continue;
             }
-            if let Some((attacker, victim)) = board.capture_pieces(mv) {
-                if piece_value(victim) < piece_value(attacker) {
-                    continue;
-                }
+            // Drop captures that lose material once all recaptures are played out
+            if board.static_exchange(mv) < 0 {
+                continue;
             }
         }
 
@@ -31,73 +39,651 @@ pub fn get_prioritized_moves(board: &Board, only_noise: bool) -> Vec<(ChessMove,
         move_priority_pairs.push((mv, priority));
     }
 
-    move_priority_pairs.sort_by(|a, b| b.1.cmp(&a.1));
+    move_priority_pairs.sort_by_key(|&(_, priority)| std::cmp::Reverse(priority));
     move_priority_pairs
 }
 
+// Half-width of the first aspiration window, in centipawns
+const ASPIRATION_WINDOW: i32 = 50;
+// Iterations below this depth are too unstable to aim a window at
+const ASPIRATION_MIN_DEPTH: u8 = 4;
+// Past this half-width the re-search falls back to a full window
+const ASPIRATION_MAX_WINDOW: i32 = 1_000;
+
 pub fn search(
-    time_left_ms: u128,
-    time_limit_ms: Option<u128>,
+    limits: &SearchLimits,
     board: &Board,
-    engine_state: &mut EngineState
-) -> (Option<ChessMove>, u64, u128, i32, u8) {
+    engine_state: &mut EngineState,
+    on_iteration: Option<&dyn Fn(&SearchResult)>
+) -> SearchResult {
+    // A reduced skill searches less, and picks its move among the top lines afterwards
+    let skill = engine_state.options.skill_level;
+    let limits = &match skill {
+        Some(skill) => weakened_limits(limits, skill),
+        None => limits.clone(),
+    };
+
     // Opening DB fallback
     let start_time = Instant::now();
-    if let Some(chess_move) = lookup_opening_db(board) {
-        return (
-            Some(chess_move),
-            0,
-            start_time.elapsed().as_millis(),
-            evaluate_board(&board.make_move_new(chess_move)),
-            0,
-        );
+    let root_moves = limits.root_moves(board);
+    if root_moves.as_ref().is_some_and(|moves| moves.is_empty()) {
+        return SearchResult::default();
     }
 
-    let start_time = Instant::now();
-    let max_time = time_limit_ms
-        .unwrap_or(time_left_ms / 40)
-        .min(time_left_ms)
-        .min(40_000);
-    let deadline = start_time + Duration::from_millis(max_time as u64);
+    let book_move = if limits.skip_book {
+        None
+    } else {
+        lookup_opening_db(board).filter(|mv| {
+            root_moves.as_ref().is_none_or(|moves| moves.contains(mv))
+        })
+    };
+    if let Some(chess_move) = book_move {
+        let eval = evaluate_board(&board.make_move_new(chess_move));
+        return SearchResult {
+            best_move: Some(chess_move),
+            pv: vec![chess_move],
+            eval,
+            time_ms: start_time.elapsed().as_millis(),
+            lines: vec![PvLine { best_move: chess_move, eval, depth: 0, pv: vec![chess_move] }],
+            ..Default::default()
+        };
+    }
 
-    let mut final_depth = 0;
-    let mut best_move = None;
-    let mut best_eval = 0;
-    let mut total_nodes = 0;
+    let mut time = TimeManager::new(limits, board, &engine_state.global_map);
+    let deadline = time.deadline();
+    let control = SearchControl {
+        deadline,
+        node_limit: limits.nodes,
+        stop: Arc::clone(&engine_state.stop),
+        root_moves,
+        white_draw_score: match limits.engine_color.unwrap_or(board.side_to_move()) {
+            chess::Color::White => -engine_state.options.contempt,
+            chess::Color::Black => engine_state.options.contempt,
+        },
+        root_best: None,
+        node_sink: None,
+    };
 
-    let color = if board.side_to_move() == chess::Color::White { 1 } else { -1 };
+    engine_state.ordering.new_search();
+    engine_state.transposition_table.new_search();
+    engine_state.search_path = vec![(board.get_hash(), engine_state.halfmove_clock)];
 
-    for depth in 1..=64 {
-        let mut nodes = 0;
-        let mut max_depth = 0;
+    // Lazy SMP: helpers search the same position and only talk through the shared TT
+    // A node budget only means the same thing twice when a single thread spends it
+    let threads = if limits.nodes.is_some() {
+        1
+    } else {
+        limits.threads.unwrap_or(engine_state.global_map.threads).max(1)
+    };
+    let helper_stop = Arc::new(AtomicBool::new(false));
+    let helper_nodes = Arc::new(AtomicU64::new(0));
+    // Reports of the main thread count what the helpers searched so far as well
+    let report_with_helpers = |partial: &SearchResult| {
+        if let Some(report) = on_iteration {
+            let mut partial = partial.clone();
+            partial.nodes += helper_nodes.load(Ordering::Relaxed);
+            report(&partial);
+        }
+    };
+
+    let mut result = thread::scope(|scope| {
+        let helpers: Vec<_> = (1..threads)
+            .map(|id| {
+                let mut helper_state = engine_state.clone();
+                let helper_control = SearchControl {
+                    deadline,
+                    node_limit: None,
+                    stop: Arc::clone(&helper_stop),
+                    root_moves: control.root_moves.clone(),
+                    white_draw_score: control.white_draw_score,
+                    root_best: None,
+                    node_sink: Some(Arc::clone(&helper_nodes)),
+                };
+                let board = *board;
+                let mut helper_time = time.clone();
+                scope.spawn(move || {
+                    // Odd helpers skip a ply so the threads do not walk the tree in lockstep
+                    let start_depth = 1 + ((id % 2) as u8);
+                    let nodes = iterative_deepening(
+                        limits,
+                        &board,
+                        &mut helper_state,
+                        &helper_control,
+                        start_depth,
+                        &mut helper_time,
+                        None
+                    ).nodes;
+                    helper_control.flush_nodes(nodes);
+                })
+            })
+            .collect();
+
+        let mut result = iterative_deepening(
+            limits,
+            board,
+            engine_state,
+            &control,
+            1,
+            &mut time,
+            on_iteration.map(|_| &report_with_helpers as &dyn Fn(&SearchResult))
+        );
 
+        helper_stop.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            helper.join().expect("search helper thread panicked");
+        }
+        result.nodes += helper_nodes.load(Ordering::Relaxed);
+        result
+    });
+
+    if let Some(skill) = skill {
+        pick_weaker_move(&mut result, skill);
+    }
+    result
+}
+
+/// One aspiration-window search of the root for a single PV line. Once the search has been
+/// stopped this is the best root move finished inside the window, if there is one.
+#[allow(clippy::too_many_arguments)]
+fn search_root_line(
+    board: &Board,
+    engine_state: &mut EngineState,
+    control: &SearchControl,
+    depth: u8,
+    center: Option<i32>,
+    nodes: &mut u64,
+    max_depth: &mut u8,
+    color: i32
+) -> Option<(ChessMove, i32, Vec<ChessMove>)> {
+    let mut pv = Vec::new();
+
+    // Aspiration window around the previous iteration's score, widened on every fail
+    let mut delta = ASPIRATION_WINDOW;
+    let (mut alpha, mut beta) = match center {
+        Some(center) if depth >= ASPIRATION_MIN_DEPTH =>
+            (
+                center.saturating_sub(delta).max(i32::MIN + 1),
+                center.saturating_add(delta).min(i32::MAX - 1),
+            ),
+        _ => (i32::MIN + 1, i32::MAX - 1),
+    };
+
+    loop {
         let (mv, eval) = negamax(
             board,
-            i32::MIN + 1,
-            i32::MAX - 1,
-            &mut nodes,
-            deadline,
+            alpha,
+            beta,
+            nodes,
+            control,
             engine_state,
             depth,
             0,
-            &mut max_depth,
-            color
+            max_depth,
+            color,
+            &mut pv,
+            None
         );
 
-        if Instant::now() >= deadline {
-            break;
+        if control.should_stop(*nodes) {
+            // A move that completed above alpha beat everything searched before it
+            return mv.filter(|_| !is_aborted(eval) && eval > alpha).map(|m| (m, eval, pv));
         }
 
-        if let Some(m) = mv {
-            best_move = Some(m);
-            best_eval = eval;
-            total_nodes += nodes;
-            final_depth = max_depth;
+        if eval <= alpha && alpha > i32::MIN + 1 {
+            alpha = eval.saturating_sub(delta).max(i32::MIN + 1);
+        } else if eval >= beta && beta < i32::MAX - 1 {
+            beta = eval.saturating_add(delta).min(i32::MAX - 1);
         } else {
+            return mv.map(|m| (m, eval, pv));
+        }
+
+        // Give up on the window entirely once it stops being narrow
+        delta = delta.saturating_mul(2);
+        if delta > ASPIRATION_MAX_WINDOW {
+            alpha = i32::MIN + 1;
+            beta = i32::MAX - 1;
+        }
+    }
+}
+
+fn iterative_deepening(
+    limits: &SearchLimits,
+    board: &Board,
+    engine_state: &mut EngineState,
+    control: &SearchControl,
+    start_depth: u8,
+    time: &mut TimeManager,
+    on_iteration: Option<&dyn Fn(&SearchResult)>
+) -> SearchResult {
+    let mut result = SearchResult::default();
+    let mut nodes = 0;
+
+    let color = if board.side_to_move() == chess::Color::White { 1 } else { -1 };
+
+    // MultiPV: line k is searched with the moves of lines 1..k taken off the root
+    let mut control = control.clone();
+    let candidates: Vec<ChessMove> = control.root_moves
+        .clone()
+        .unwrap_or_else(|| MoveGen::new_legal(board).collect());
+    let multipv = limits.multipv.unwrap_or(1).clamp(1, candidates.len().max(1));
+
+    // A mate in N moves takes 2N - 1 plies, reductions can push it past that so the search
+    // goes on to twice as deep before giving up. Depth 0 still searches one ply, there would
+    // be no move to answer with otherwise.
+    let last_depth = limits.mate
+        .map(|moves| ((moves.max(1) as u16) * 2 - 1) * 2)
+        .map(|plies| plies.min(u8::MAX as u16) as u8)
+        .into_iter()
+        .chain(limits.depth.map(|depth| depth.max(1)))
+        .min()
+        .unwrap_or(64);
+
+    'deepening: for depth in start_depth..=last_depth {
+        let iteration_start = Instant::now();
+        let mut max_depth = 0;
+        let mut lines: Vec<PvLine> = Vec::with_capacity(multipv);
+
+        for pv_index in 0..multipv {
+            if multipv > 1 {
+                control.root_moves = Some(
+                    candidates
+                        .iter()
+                        .filter(|&&mv| lines.iter().all(|line| line.best_move != mv))
+                        .copied()
+                        .collect()
+                );
+            }
+
+            // The TT does not keep a restricted root, so the previous choice is passed on directly
+            control.root_best = result.lines.get(pv_index).map(|line| line.best_move);
+            let center = result.lines.get(pv_index).map(|line| line.eval);
+            let line = search_root_line(
+                board,
+                engine_state,
+                &control,
+                depth,
+                center,
+                &mut nodes,
+                &mut max_depth,
+                color
+            );
+
+            if control.should_stop(nodes) {
+                // Only the main line of an interrupted iteration is worth keeping. The previous
+                // best move is searched first, so any other move returned here has beaten it.
+                if let Some((best_move, eval, pv)) = line.filter(|_| pv_index == 0) {
+                    if result.best_move != Some(best_move) {
+                        result.best_move = Some(best_move);
+                        result.eval = eval;
+                        result.pv = pv.clone();
+                        result.lines.retain(|line| line.best_move != best_move);
+                        result.lines.insert(0, PvLine { best_move, eval, depth, pv });
+                        result.lines.truncate(multipv);
+                        result.seldepth = result.seldepth.max(max_depth);
+                    }
+                }
+                break 'deepening;
+            }
+
+            let Some((best_move, eval, pv)) = line else {
+                break 'deepening;
+            };
+            lines.push(PvLine { best_move, eval, depth, pv });
+        }
+
+        lines.sort_by_key(|line| std::cmp::Reverse(line.eval));
+        result.best_move = Some(lines[0].best_move);
+        result.eval = lines[0].eval;
+        result.pv = lines[0].pv.clone();
+        result.depth = depth;
+        result.seldepth = max_depth;
+        result.lines = lines;
+
+        result.nodes = nodes;
+        result.time_ms = time.start.elapsed().as_millis();
+        result.hashfull = engine_state.transposition_table.hashfull();
+        if let Some(report) = on_iteration {
+            report(&result);
+        }
+
+        if let (Some(target), Some(moves)) = (limits.mate, mate_in_moves(result.eval)) {
+            if moves > 0 && moves <= (target as i32) {
+                break;
+            }
+        }
+
+        time.update(result.best_move, result.eval);
+        if time.stop_after_iteration(iteration_start.elapsed()) {
             break;
         }
     }
 
-    let time_taken = start_time.elapsed().as_millis();
-    (best_move, total_nodes, time_taken, best_eval, final_depth)
+    // Stopped before the first iteration finished: any legal move beats none at all, and the
+    // TT move is the one most likely to be good
+    if result.best_move.is_none() {
+        let tt_move = engine_state.transposition_table
+            .get(board.get_hash())
+            .and_then(|entry| entry.best_move)
+            .filter(|mv| candidates.contains(mv));
+        let ordered_move = || {
+            get_prioritized_moves(board, false)
+                .into_iter()
+                .map(|(mv, _)| mv)
+                .find(|mv| candidates.contains(mv))
+        };
+        if let Some(best_move) = tt_move.or_else(ordered_move) {
+            let eval = evaluate_board(&board.make_move_new(best_move)) * color;
+            result.best_move = Some(best_move);
+            result.eval = eval;
+            result.pv = vec![best_move];
+            result.lines = vec![PvLine { best_move, eval, depth: 0, pv: vec![best_move] }];
+        }
+    }
+
+    result.nodes = nodes;
+    result.time_ms = time.start.elapsed().as_millis();
+    result.hashfull = engine_state.transposition_table.hashfull();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::BoardStatus;
+    use std::str::FromStr;
+    use crate::bot::include::types::{ GlobalMap, RepetitionHistory, TranspositionTable };
+
+    fn engine(fen: &str) -> (Board, EngineState) {
+        let board = Board::from_str(fen).unwrap();
+        let mut history = RepetitionHistory::new();
+        history.increment(board.get_hash());
+        let engine_state = EngineState::new(
+            "test".to_string(),
+            board,
+            history,
+            Arc::new(GlobalMap::default()),
+            TranspositionTable::new(1)
+        );
+        (board, engine_state)
+    }
+
+    fn control() -> SearchControl {
+        SearchControl {
+            deadline: None,
+            node_limit: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            root_moves: None,
+            white_draw_score: 0,
+            root_best: None,
+            node_sink: None,
+        }
+    }
+
+    fn depth_limits(depth: u8) -> SearchLimits {
+        SearchLimits { depth: Some(depth), skip_book: true, ..Default::default() }
+    }
+
+    // Plays `pv` from `board`, panicking on the first illegal move
+    fn play_line(board: &Board, pv: &[ChessMove]) -> Board {
+        pv.iter().fold(*board, |board, &mv| {
+            assert!(board.legal(mv), "{} is illegal in the PV", mv);
+            board.make_move_new(mv)
+        })
+    }
+
+    #[test]
+    fn pv_is_a_legal_line_from_the_best_move() {
+        let (board, mut engine_state) = engine(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+        );
+        let result = search(&depth_limits(5), &board, &mut engine_state, None);
+
+        assert_eq!(result.depth, 5);
+        assert_eq!(result.pv.first().copied(), result.best_move);
+        assert_eq!(result.lines[0].pv, result.pv);
+        // Every ply of the search shows up, PV nodes never cut the line short
+        assert!(result.pv.len() >= 5, "PV {:?} is shorter than the depth", result.pv);
+        play_line(&board, &result.pv);
+    }
+
+    #[test]
+    fn aspiration_window_off_the_score_still_finds_it() {
+        // Kb6 or Kc7 first, mate with the rook on the next move
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let line = |center: Option<i32>| {
+            let (board, mut engine_state) = engine(fen);
+            engine_state.search_path = vec![(board.get_hash(), 0)];
+            search_root_line(&board, &mut engine_state, &control(), 5, center, &mut 0, &mut 0, 1)
+                .expect("no move found")
+        };
+
+        let (_, full_window, _) = line(None);
+        assert_eq!(mate_in_moves(full_window), Some(2));
+        // Fails high out of every narrow window until it is wide enough
+        for center in [0, -5_000, 5_000, full_window - 100] {
+            let (best_move, eval, pv) = line(Some(center));
+            assert_eq!(eval, full_window, "window around {}", center);
+            assert_eq!(pv.first(), Some(&best_move));
+        }
+    }
+
+    #[test]
+    fn narrow_windows_keep_the_winning_capture() {
+        // The queen on g5 hangs to the bishop
+        let (board, mut engine_state) = engine(
+            "rnb1kbnr/pppp1ppp/8/4p1q1/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        let result = search(&depth_limits(6), &board, &mut engine_state, None);
+
+        assert_eq!(result.best_move, Some(ChessMove::from_str("c1g5").unwrap()));
+        assert!(result.eval > 500, "eval {} after winning the queen", result.eval);
+    }
+
+    #[test]
+    fn every_iteration_is_reported_with_its_line() {
+        let (board, mut engine_state) = engine(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+        );
+        let reports = std::cell::RefCell::new(Vec::new());
+        let report = |result: &SearchResult| {
+            assert_eq!(result.pv.first().copied(), result.best_move);
+            reports.borrow_mut().push(result.depth);
+        };
+        search(&depth_limits(4), &board, &mut engine_state, Some(&report));
+
+        assert_eq!(reports.into_inner(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn stop_before_depth_one_still_returns_a_move() {
+        let (board, mut engine_state) = engine(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        engine_state.stop.store(true, Ordering::Relaxed);
+        let limits = SearchLimits { infinite: true, skip_book: true, ..Default::default() };
+
+        let result = search(&limits, &board, &mut engine_state, None);
+        let best_move = result.best_move.expect("no move after an early stop");
+        assert!(board.legal(best_move));
+        assert_eq!(result.pv, vec![best_move]);
+    }
+
+    #[test]
+    fn early_stop_keeps_to_the_restricted_root() {
+        let (board, mut engine_state) = engine(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        engine_state.stop.store(true, Ordering::Relaxed);
+        let h2h3 = ChessMove::from_str("h2h3").unwrap();
+        let limits = SearchLimits {
+            infinite: true,
+            skip_book: true,
+            search_moves: Some(vec![h2h3]),
+            ..Default::default()
+        };
+
+        let result = search(&limits, &board, &mut engine_state, None);
+        assert_eq!(result.best_move, Some(h2h3));
+    }
+
+    #[test]
+    fn helper_threads_agree_on_the_mate() {
+        let (board, mut engine_state) = engine("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+        let limits = SearchLimits { threads: Some(3), ..depth_limits(6) };
+        let result = search(&limits, &board, &mut engine_state, None);
+
+        assert_eq!(mate_in_moves(result.eval), Some(2));
+        assert_eq!(result.depth, 6);
+        play_line(&board, &result.pv);
+        assert!(result.nodes > 0);
+    }
+
+    #[test]
+    fn multipv_lines_are_distinct_and_sorted() {
+        let (board, mut engine_state) = engine(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+        );
+        let limits = SearchLimits { multipv: Some(3), ..depth_limits(4) };
+        let result = search(&limits, &board, &mut engine_state, None);
+
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].best_move, result.best_move.unwrap());
+        assert_eq!(result.lines[0].eval, result.eval);
+        for (i, line) in result.lines.iter().enumerate() {
+            assert_eq!(line.depth, 4);
+            assert_eq!(line.pv.first(), Some(&line.best_move));
+            play_line(&board, &line.pv);
+            for other in &result.lines[i + 1..] {
+                assert_ne!(line.best_move, other.best_move);
+                assert!(line.eval >= other.eval, "{} listed before {}", line.eval, other.eval);
+            }
+        }
+    }
+
+    #[test]
+    fn multipv_stops_at_the_legal_moves() {
+        // Taking the rook is the only legal move
+        let (board, mut engine_state) = engine("k7/8/8/8/8/8/1r6/K7 w - - 0 1");
+        let limits = SearchLimits { multipv: Some(5), ..depth_limits(3) };
+        let result = search(&limits, &board, &mut engine_state, None);
+
+        assert_eq!(result.lines.len(), 1);
+        assert_eq!(result.best_move, Some(ChessMove::from_str("a1b2").unwrap()));
+    }
+
+    #[test]
+    fn root_restrictions_are_respected() {
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let mv = |uci: &str| ChessMove::from_str(uci).unwrap();
+
+        let (board, mut engine_state) = engine(fen);
+        let limits = SearchLimits {
+            search_moves: Some(vec![mv("h1h2"), mv("c6d5")]),
+            multipv: Some(3),
+            ..depth_limits(4)
+        };
+        let result = search(&limits, &board, &mut engine_state, None);
+        assert_eq!(result.lines.len(), 2);
+        for line in &result.lines {
+            assert!([mv("h1h2"), mv("c6d5")].contains(&line.best_move));
+        }
+
+        // Without the king moves that mate in two, the mate takes longer
+        let (board, mut engine_state) = engine(fen);
+        let limits = SearchLimits {
+            excluded_moves: vec![mv("c6b6"), mv("c6c7")],
+            ..depth_limits(6)
+        };
+        let result = search(&limits, &board, &mut engine_state, None);
+        assert!(!limits.excluded_moves.contains(&result.best_move.unwrap()));
+        assert!(mate_in_moves(result.eval).is_none_or(|moves| moves > 2));
+    }
+
+    #[test]
+    fn mates_are_reported_in_moves_with_their_line() {
+        let (board, mut engine_state) = engine("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+        let result = search(&depth_limits(6), &board, &mut engine_state, None);
+        assert_eq!(mate_in_moves(result.eval), Some(2));
+        assert_eq!(result.pv.len(), 3);
+        assert_eq!(play_line(&board, &result.pv).status(), BoardStatus::Checkmate);
+
+        // Kb8 is forced, then Rh8 mates
+        let (board, mut engine_state) = engine("k7/8/1K6/8/8/8/8/7R b - - 0 1");
+        let result = search(&depth_limits(6), &board, &mut engine_state, None);
+        assert_eq!(mate_in_moves(result.eval), Some(-1));
+        assert_eq!(result.pv.len(), 2);
+        assert_eq!(play_line(&board, &result.pv).status(), BoardStatus::Checkmate);
+    }
+
+    #[test]
+    fn depth_limits_are_met_exactly() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        // Depth 0 still searches one ply, there would be no move otherwise
+        for (depth, searched) in [(0, 1), (1, 1), (3, 3)] {
+            let (board, mut engine_state) = engine(fen);
+            let result = search(&depth_limits(depth), &board, &mut engine_state, None);
+            assert_eq!(result.depth, searched, "depth {}", depth);
+            assert!(board.legal(result.best_move.expect("no move")));
+        }
+    }
+
+    #[test]
+    fn node_limit_ends_the_search() {
+        let (board, mut engine_state) = engine(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+        );
+        let limits = SearchLimits { nodes: Some(5_000), threads: Some(3), ..Default::default() };
+        let result = search(&limits, &board, &mut engine_state, None);
+        assert!(result.nodes <= 5_000, "{} nodes searched", result.nodes);
+        assert!(board.legal(result.best_move.expect("no move")));
+    }
+
+    #[test]
+    fn mate_limit_stops_once_the_mate_is_found() {
+        let (board, mut engine_state) = engine("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+        let limits = SearchLimits { mate: Some(2), skip_book: true, ..Default::default() };
+        let result = search(&limits, &board, &mut engine_state, None);
+        assert_eq!(mate_in_moves(result.eval), Some(2));
+        // Found at depth 5, LMR hides it from shallower searches
+        assert_eq!(result.depth, 5);
+
+        // No mate to find, the search gives up at twice the plies of a mate in one
+        let (board, mut engine_state) = engine(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+        );
+        let limits = SearchLimits { mate: Some(1), skip_book: true, ..Default::default() };
+        let result = search(&limits, &board, &mut engine_state, None);
+        assert_eq!(mate_in_moves(result.eval), None);
+        assert_eq!(result.depth, 2);
+    }
+
+    #[test]
+    fn repetitions_score_as_the_contempt_draw() {
+        // Every move repeats a position seen twice already, the search only finds draws
+        let draw_eval = |contempt: i32, engine_color: Option<chess::Color>| {
+            let (board, mut engine_state) = engine(
+                "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+            );
+            for mv in MoveGen::new_legal(&board) {
+                engine_state.history.set(board.make_move_new(mv).get_hash(), 2);
+            }
+            engine_state.options.contempt = contempt;
+            let limits = SearchLimits { engine_color, ..depth_limits(2) };
+            search(&limits, &board, &mut engine_state, None).eval
+        };
+
+        assert_eq!(draw_eval(0, None), 0);
+        // The engine avoids draws it thinks it is too strong for, and welcomes them otherwise
+        assert_eq!(draw_eval(50, None), -50);
+        assert_eq!(draw_eval(-30, None), 30);
+        // Analysing for the other side turns the contempt around
+        assert_eq!(draw_eval(50, Some(chess::Color::Black)), 50);
+    }
+
+    #[test]
+    fn draw_score_is_relative_to_the_side_to_move() {
+        let control = SearchControl { white_draw_score: -50, ..control() };
+        assert_eq!(control.draw_score(1), -50);
+        assert_eq!(control.draw_score(-1), 50);
+    }
 }
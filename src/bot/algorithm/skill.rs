@@ -0,0 +1,158 @@
+use rand::Rng;
+use crate::bot::include::types::{ SearchLimits, SearchResult };
+use crate::bot::util::score::is_mate_score;
+
+/// Full strength, no handicap at all
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+// Elo range the skill levels are spread over
+const MIN_ELO: u32 = 800;
+const MAX_ELO: u32 = 2400;
+// Candidate lines a handicapped search chooses from
+const SKILL_MULTIPV: usize = 4;
+// Score gap between the top lines that counts for the random part of the push, the floor
+// lets lines that score the same take turns
+const MIN_DELTA: i32 = 10;
+const MAX_DELTA: i32 = 100;
+
+pub fn skill_from_elo(elo: u32) -> u8 {
+    let elo = elo.clamp(MIN_ELO, MAX_ELO);
+    (((elo - MIN_ELO) * (MAX_SKILL_LEVEL as u32)) / (MAX_ELO - MIN_ELO)) as u8
+}
+
+/// Shallower, cheaper search for a skill level, with enough lines to pick a mistake from
+pub fn weakened_limits(limits: &SearchLimits, skill: u8) -> SearchLimits {
+    let skill = skill.min(MAX_SKILL_LEVEL);
+    let max_depth = 2 + skill / 2;
+    let max_nodes = 1_000 * ((skill as u64) + 1).pow(2);
+
+    SearchLimits {
+        depth: Some(limits.depth.map_or(max_depth, |depth| depth.min(max_depth))),
+        nodes: Some(limits.nodes.map_or(max_nodes, |nodes| nodes.min(max_nodes))),
+        multipv: Some(limits.multipv.unwrap_or(1).max(SKILL_MULTIPV)),
+        ..limits.clone()
+    }
+}
+
+/// Swaps the best move for one of the other lines, more often and further down the list the
+/// lower the skill. Lines are pushed up by a share of what they lose, so the engine prefers
+/// near misses over outright blunders.
+pub fn pick_weaker_move(result: &mut SearchResult, skill: u8) {
+    // A forced mate is always played, lines walking into one are never picked
+    if result.lines.first().is_none_or(|top| is_mate_score(top.eval)) {
+        return;
+    }
+    let candidates: Vec<_> = result.lines
+        .iter()
+        .filter(|line| !is_mate_score(line.eval))
+        .collect();
+
+    let weakness = 120 - 2 * (skill.min(MAX_SKILL_LEVEL) as i32);
+    let top_eval = candidates[0].eval;
+    let worst_eval = candidates[candidates.len() - 1].eval;
+    let delta = (top_eval - worst_eval).clamp(MIN_DELTA, MAX_DELTA);
+
+    let mut rng = rand::thread_rng();
+    let Some(chosen) = candidates
+        .into_iter()
+        .max_by_key(|line| {
+            let push = (weakness * (top_eval - line.eval) + delta * rng.gen_range(0..weakness)) / 128;
+            line.eval + push
+        })
+        .cloned() else {
+        return;
+    };
+
+    result.best_move = Some(chosen.best_move);
+    result.eval = chosen.eval;
+    result.pv = chosen.pv;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::ChessMove;
+    use std::str::FromStr;
+    use crate::bot::include::types::PvLine;
+    use crate::bot::util::score::MATE_SCORE;
+
+    fn result(lines: &[(&str, i32)]) -> SearchResult {
+        let lines: Vec<PvLine> = lines
+            .iter()
+            .map(|&(uci, eval)| {
+                let best_move = ChessMove::from_str(uci).unwrap();
+                PvLine { best_move, eval, depth: 4, pv: vec![best_move] }
+            })
+            .collect();
+        SearchResult {
+            best_move: Some(lines[0].best_move),
+            eval: lines[0].eval,
+            pv: lines[0].pv.clone(),
+            lines,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn elo_maps_onto_the_skill_levels() {
+        assert_eq!(skill_from_elo(0), 0);
+        assert_eq!(skill_from_elo(MIN_ELO), 0);
+        assert_eq!(skill_from_elo(1_600), 10);
+        assert_eq!(skill_from_elo(MAX_ELO), MAX_SKILL_LEVEL);
+        assert_eq!(skill_from_elo(3_500), MAX_SKILL_LEVEL);
+    }
+
+    #[test]
+    fn weakened_limits_only_ever_tighten() {
+        let limits = SearchLimits { time_left_ms: Some(60_000), ..Default::default() };
+        let weakest = weakened_limits(&limits, 0);
+        assert_eq!(weakest.depth, Some(2));
+        assert_eq!(weakest.nodes, Some(1_000));
+        assert_eq!(weakest.multipv, Some(4));
+        assert_eq!(weakest.time_left_ms, Some(60_000));
+
+        let strongest = weakened_limits(&limits, MAX_SKILL_LEVEL);
+        assert_eq!((strongest.depth, strongest.nodes), (Some(12), Some(441_000)));
+
+        // Tighter limits and more lines asked for are kept
+        let tight = SearchLimits { depth: Some(1), nodes: Some(500), multipv: Some(6), ..limits };
+        let weakened = weakened_limits(&tight, 10);
+        assert_eq!(weakened.depth, Some(1));
+        assert_eq!(weakened.nodes, Some(500));
+        assert_eq!(weakened.multipv, Some(6));
+    }
+
+    #[test]
+    fn forced_mates_are_always_played() {
+        let mut mating = result(&[("h1h8", MATE_SCORE - 1), ("h1h7", 600)]);
+        for _ in 0..50 {
+            pick_weaker_move(&mut mating, 0);
+            assert_eq!(mating.best_move, Some(ChessMove::from_str("h1h8").unwrap()));
+        }
+    }
+
+    #[test]
+    fn weaker_moves_come_from_the_lines() {
+        let mut picked = Vec::new();
+        let lines = [
+            ("e2e4", 50),
+            ("d2d4", 40),
+            ("g1f3", 30),
+            ("a2a3", 20),
+            ("f2f3", -MATE_SCORE + 2),
+        ];
+        for _ in 0..200 {
+            let mut weak = result(&lines);
+            pick_weaker_move(&mut weak, 0);
+            let line = weak.lines
+                .iter()
+                .find(|line| Some(line.best_move) == weak.best_move)
+                .unwrap();
+            assert_eq!((weak.eval, &weak.pv), (line.eval, &line.pv));
+            // Walking into a mate is never a near miss
+            assert_ne!(line.eval, -MATE_SCORE + 2);
+            picked.push(line.best_move);
+        }
+        assert!(picked.iter().any(|&mv| mv != ChessMove::from_str("e2e4").unwrap()));
+    }
+}
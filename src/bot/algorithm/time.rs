@@ -0,0 +1,222 @@
+use chess::{ Board, ChessMove };
+use std::time::{ Duration, Instant };
+use crate::bot::include::types::{ GlobalMap, SearchLimits, TimeManager };
+use crate::bot::util::board::{ BoardExt, MAX_PHASE };
+
+// Moves still to play when the clock does not say, from a full board down to a bare ending
+const MOVES_TO_GO_OPENING: u128 = 40;
+const MOVES_TO_GO_ENDING: u128 = 20;
+// The hard limit may stretch the optimum this far ...
+const HARD_LIMIT_FACTOR: u128 = 4;
+// ... but never past this share of the clock, unless the move is the last before the time control
+const HARD_LIMIT_CLOCK_PERCENT: u128 = 75;
+// An iteration takes at least this many times as long as the one before it
+const ITERATION_GROWTH: u32 = 2;
+// A score drop this large gets the full extension
+const SCORE_DROP_MAX: i32 = 200;
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, board: &Board, global_map: &GlobalMap) -> Self {
+        let (soft_ms, hard_ms) = match limits.infinite {
+            true => (None, None),
+            false => Self::limits_ms(limits, board, global_map),
+        };
+
+        Self {
+            start: Instant::now(),
+            soft_ms,
+            hard_ms,
+            scale: 1.0,
+            best_move: None,
+            stable_iterations: 0,
+            previous_eval: None,
+        }
+    }
+
+    fn limits_ms(
+        limits: &SearchLimits,
+        board: &Board,
+        global_map: &GlobalMap
+    ) -> (Option<u128>, Option<u128>) {
+        let overhead = limits.move_overhead_ms.unwrap_or(global_map.move_overhead_ms);
+
+        let clock = limits.time_left_ms.map(|left| {
+            let usable = left.saturating_sub(overhead).max(1);
+
+            // Spend less per move while many pieces, and so many moves, are left
+            let phase = board.game_phase() as u128;
+            let moves_to_go = limits.moves_to_go.map(|m| m.max(1) as u128).unwrap_or(
+                MOVES_TO_GO_ENDING +
+                    ((MOVES_TO_GO_OPENING - MOVES_TO_GO_ENDING) * phase) / (MAX_PHASE as u128)
+            );
+
+            let mut optimum = usable / moves_to_go + (limits.increment_ms * 3) / 4;
+            // Ahead on the clock we can afford to think longer, behind we have to hurry
+            if let Some(opponent) = limits.opponent_time_ms.filter(|&t| t > 0) {
+                let ratio = ((left as f64) / (opponent as f64)).clamp(0.8, 1.25);
+                optimum = ((optimum as f64) * ratio) as u128;
+            }
+            let optimum = optimum.clamp(1, usable);
+
+            let hard = (optimum * HARD_LIMIT_FACTOR)
+                .min((usable * HARD_LIMIT_CLOCK_PERCENT) / 100)
+                .max(optimum);
+            (optimum, hard)
+        });
+
+        // A fixed time per move is a limit on its own, and caps the clock-based one
+        let fixed = limits.time_limit_ms.map(|limit| limit.saturating_sub(overhead).max(1));
+
+        let (soft, hard) = match (clock, fixed) {
+            (Some((optimum, hard)), Some(fixed)) => (Some(optimum.min(fixed)), Some(hard.min(fixed))),
+            (Some((optimum, hard)), None) => (Some(optimum), Some(hard)),
+            // No point stopping early on a fixed time, it is not saved for later moves
+            (None, fixed) => (None, fixed),
+        };
+
+        let cap = |ms: u128| global_map.max_move_time_ms.map_or(ms, |max| ms.min(max));
+        (soft.map(cap), hard.map(cap))
+    }
+
+    /// Instant the search has to be stopped at, wherever it is
+    pub fn deadline(&self) -> Option<Instant> {
+        self.hard_ms.map(|ms| self.start + Duration::from_millis(ms as u64))
+    }
+
+    /// Feeds the result of a completed iteration into the soft limit
+    pub fn update(&mut self, best_move: Option<ChessMove>, eval: i32) {
+        // A best move that keeps changing needs more time, a settled one less
+        let flipped = self.best_move.is_some() && best_move != self.best_move;
+        self.stable_iterations = if best_move == self.best_move {
+            self.stable_iterations + 1
+        } else {
+            0
+        };
+        let stability = match (flipped, self.stable_iterations) {
+            (true, _) => 1.4,
+            (false, 0..=1) => 1.0,
+            (false, 2..=3) => 0.85,
+            _ => 0.7,
+        };
+
+        // So does a score that just dropped, until the search finds its way out
+        let drop = self.previous_eval.map_or(0, |previous| previous - eval);
+        let score = 1.0 + (drop.clamp(0, SCORE_DROP_MAX) as f64) / (SCORE_DROP_MAX as f64) * 0.5;
+
+        self.scale = (stability * score).clamp(0.5, 2.0);
+        self.best_move = best_move;
+        self.previous_eval = Some(eval);
+    }
+
+    /// True when the next iteration should not be started, given how long the last one took
+    pub fn stop_after_iteration(&self, last_iteration: Duration) -> bool {
+        let elapsed = self.start.elapsed();
+
+        let soft_reached = self.soft_ms.is_some_and(|soft| {
+            elapsed.as_secs_f64() * 1000.0 >= (soft as f64) * self.scale
+        });
+        let next_cannot_finish = self.hard_ms.is_some_and(|hard| {
+            elapsed + last_iteration * ITERATION_GROWTH > Duration::from_millis(hard as u64)
+        });
+
+        soft_reached || next_cannot_finish
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // Overhead of 30 ms, as `GlobalMap::default` has it
+    fn limits_ms(limits: SearchLimits) -> (Option<u128>, Option<u128>) {
+        let manager = TimeManager::new(&limits, &Board::default(), &GlobalMap::default());
+        (manager.soft_ms, manager.hard_ms)
+    }
+
+    fn clock(time_left_ms: u128) -> SearchLimits {
+        SearchLimits { time_left_ms: Some(time_left_ms), ..Default::default() }
+    }
+
+    #[test]
+    fn fixed_move_time_is_a_hard_limit_only() {
+        let limits = SearchLimits { time_limit_ms: Some(1_000), ..Default::default() };
+        assert_eq!(limits_ms(limits), (None, Some(970)));
+        // It still caps what the clock would allow
+        let (soft, hard) = limits_ms(SearchLimits { time_limit_ms: Some(200), ..clock(60_000) });
+        assert_eq!((soft, hard), (Some(170), Some(170)));
+    }
+
+    #[test]
+    fn clock_time_is_spread_over_the_moves_left() {
+        // 40 moves to go on a full board
+        let (soft, hard) = limits_ms(clock(60_000));
+        assert_eq!(soft, Some(59_970 / 40));
+        assert_eq!(hard, Some((59_970 / 40) * HARD_LIMIT_FACTOR));
+
+        // Short on time the hard limit stays well inside the clock ...
+        let (soft, hard) = limits_ms(SearchLimits { moves_to_go: Some(2), ..clock(1_000) });
+        assert!(soft < hard);
+        assert_eq!(hard, Some((970 * HARD_LIMIT_CLOCK_PERCENT) / 100));
+        // ... unless the time control is over after this move
+        let (soft, hard) = limits_ms(SearchLimits { moves_to_go: Some(1), ..clock(1_000) });
+        assert_eq!((soft, hard), (Some(970), Some(970)));
+    }
+
+    #[test]
+    fn increment_and_clock_lead_buy_time() {
+        let (base, _) = limits_ms(clock(60_000));
+        let (with_increment, _) = limits_ms(SearchLimits { increment_ms: 2_000, ..clock(60_000) });
+        assert_eq!(with_increment, base.map(|ms| ms + 1_500));
+
+        let ahead = SearchLimits { opponent_time_ms: Some(30_000), ..clock(60_000) };
+        let behind = SearchLimits { opponent_time_ms: Some(120_000), ..clock(60_000) };
+        assert_eq!(limits_ms(ahead).0, base.map(|ms| ((ms as f64) * 1.25) as u128));
+        assert_eq!(limits_ms(behind).0, base.map(|ms| ((ms as f64) * 0.8) as u128));
+    }
+
+    #[test]
+    fn max_move_time_caps_both_limits() {
+        let global_map = GlobalMap { max_move_time_ms: Some(500), ..Default::default() };
+        let manager = TimeManager::new(&clock(600_000), &Board::default(), &global_map);
+        assert_eq!((manager.soft_ms, manager.hard_ms), (Some(500), Some(500)));
+    }
+
+    #[test]
+    fn infinite_search_has_no_deadline() {
+        let limits = SearchLimits { infinite: true, ..clock(1_000) };
+        let manager = TimeManager::new(&limits, &Board::default(), &GlobalMap::default());
+        assert_eq!(manager.deadline(), None);
+        assert!(!manager.stop_after_iteration(Duration::from_secs(3_600)));
+    }
+
+    #[test]
+    fn unstable_searches_get_more_time() {
+        let global_map = GlobalMap::default();
+        let mut manager = TimeManager::new(&clock(60_000), &Board::default(), &global_map);
+        let e2e4 = ChessMove::from_str("e2e4").unwrap();
+        let d2d4 = ChessMove::from_str("d2d4").unwrap();
+
+        manager.update(Some(e2e4), 30);
+        assert_eq!(manager.scale, 1.0);
+        for _ in 0..4 {
+            manager.update(Some(e2e4), 30);
+        }
+        assert_eq!(manager.scale, 0.7);
+
+        manager.update(Some(d2d4), 30);
+        assert_eq!(manager.scale, 1.4);
+        // A score drop on top of a changed move runs into the upper bound
+        manager.update(Some(e2e4), -500);
+        assert_eq!(manager.scale, 2.0);
+    }
+
+    #[test]
+    fn no_iteration_is_started_that_cannot_finish() {
+        let limits = SearchLimits { time_limit_ms: Some(1_030), ..Default::default() };
+        let manager = TimeManager::new(&limits, &Board::default(), &GlobalMap::default());
+        assert!(!manager.stop_after_iteration(Duration::from_millis(100)));
+        // Twice the last iteration would not fit in the second left
+        assert!(manager.stop_after_iteration(Duration::from_millis(600)));
+    }
+}
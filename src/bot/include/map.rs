@@ -49,7 +49,8 @@ impl GlobalMap {
     }
 
     // NOTE: All these assume that Index 0 === a1 ( Top-Left of the board )
-    pub const PAWN_TABLE: [[i32; 8]; 8] = [
+    // _START tables score the middlegame, _END tables the endgame, the eval blends both by phase
+    pub const PAWN_TABLE_START: [[i32; 8]; 8] = [
         [0, 0, 0, 0, 0, 0, 0, 0],
         [5, 10, 10, -20, -20, 10, 10, 5],
         [5, -5, -10, 0, 0, -10, -5, 5],
@@ -60,7 +61,7 @@ impl GlobalMap {
         [0, 0, 0, 0, 0, 0, 0, 0],
     ];
 
-    pub const KNIGHT_TABLE: [[i32; 8]; 8] = [
+    pub const KNIGHT_TABLE_START: [[i32; 8]; 8] = [
         [-50, -40, -30, -30, -30, -30, -40, -50],
         [-40, -20, 0, 5, 5, 0, -20, -40],
         [-30, 5, 10, 15, 15, 10, 5, -30],
@@ -71,7 +72,7 @@ impl GlobalMap {
         [-50, -40, -30, -30, -30, -30, -40, -50],
     ];
 
-    pub const BISHOP_TABLE: [[i32; 8]; 8] = [
+    pub const BISHOP_TABLE_START: [[i32; 8]; 8] = [
         [-20, -10, -10, -10, -10, -10, -10, -20],
         [-10, 5, 0, 0, 0, 0, 5, -10],
         [-10, 10, 10, 10, 10, 10, 10, -10],
@@ -82,7 +83,7 @@ impl GlobalMap {
         [-20, -10, -10, -10, -10, -10, -10, -20],
     ];
 
-    pub const ROOK_TABLE: [[i32; 8]; 8] = [
+    pub const ROOK_TABLE_START: [[i32; 8]; 8] = [
         [0, 0, 0, 5, 5, 0, 0, 0],
         [-5, 0, 0, 0, 0, 0, 0, -5],
         [-5, 0, 0, 0, 0, 0, 0, -5],
@@ -93,7 +94,7 @@ impl GlobalMap {
         [0, 0, 0, 0, 0, 0, 0, 0],
     ];
 
-    pub const QUEEN_TABLE: [[i32; 8]; 8] = [
+    pub const QUEEN_TABLE_START: [[i32; 8]; 8] = [
         [-20, -10, -10, -5, -5, -10, -10, -20],
         [-10, 0, 5, 0, 0, 0, 0, -10],
         [-10, 5, 5, 5, 5, 5, 0, -10],
@@ -104,6 +105,61 @@ impl GlobalMap {
         [-20, -10, -10, -5, -5, -10, -10, -20],
     ];
 
+    pub const PAWN_TABLE_END: [[i32; 8]; 8] = [
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [5, 5, 5, 5, 5, 5, 5, 5],
+        [5, 5, 5, 5, 5, 5, 5, 5],
+        [10, 10, 10, 10, 10, 10, 10, 10],
+        [20, 20, 20, 20, 20, 20, 20, 20],
+        [35, 35, 35, 35, 35, 35, 35, 35],
+        [60, 60, 60, 60, 60, 60, 60, 60],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+    ];
+
+    pub const KNIGHT_TABLE_END: [[i32; 8]; 8] = [
+        [-40, -30, -20, -20, -20, -20, -30, -40],
+        [-30, -15, -5, 0, 0, -5, -15, -30],
+        [-20, -5, 5, 10, 10, 5, -5, -20],
+        [-20, 0, 10, 15, 15, 10, 0, -20],
+        [-20, 0, 10, 15, 15, 10, 0, -20],
+        [-20, -5, 5, 10, 10, 5, -5, -20],
+        [-30, -15, -5, 0, 0, -5, -15, -30],
+        [-40, -30, -20, -20, -20, -20, -30, -40],
+    ];
+
+    pub const BISHOP_TABLE_END: [[i32; 8]; 8] = [
+        [-15, -10, -10, -10, -10, -10, -10, -15],
+        [-10, 0, 0, 0, 0, 0, 0, -10],
+        [-10, 0, 5, 5, 5, 5, 0, -10],
+        [-10, 0, 5, 10, 10, 5, 0, -10],
+        [-10, 0, 5, 10, 10, 5, 0, -10],
+        [-10, 0, 5, 5, 5, 5, 0, -10],
+        [-10, 0, 0, 0, 0, 0, 0, -10],
+        [-15, -10, -10, -10, -10, -10, -10, -15],
+    ];
+
+    pub const ROOK_TABLE_END: [[i32; 8]; 8] = [
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [5, 5, 5, 5, 5, 5, 5, 5],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+    ];
+
+    pub const QUEEN_TABLE_END: [[i32; 8]; 8] = [
+        [-20, -15, -10, -10, -10, -10, -15, -20],
+        [-15, -5, 0, 0, 0, 0, -5, -15],
+        [-10, 0, 5, 5, 5, 5, 0, -10],
+        [-10, 0, 5, 10, 10, 5, 0, -10],
+        [-10, 0, 5, 10, 10, 5, 0, -10],
+        [-10, 0, 5, 5, 5, 5, 0, -10],
+        [-15, -5, 0, 0, 0, 0, -5, -15],
+        [-20, -15, -10, -10, -10, -10, -15, -20],
+    ];
+
     pub const KING_TABLE_START: [[i32; 8]; 8] = [
         [20, 30, 10, 0, 0, 10, 30, 20],
         [20, 20, 0, 0, 0, 0, 20, 20],
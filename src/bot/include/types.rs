@@ -1,9 +1,10 @@
 use chess::{ Board, ChessMove };
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::sync::{ Arc, Mutex };
+use std::path::PathBuf;
+use std::sync::{ atomic::{ AtomicBool, AtomicU64, AtomicU8, AtomicUsize }, Arc, Mutex };
+use std::time::Instant;
 use dashmap::DashMap;
-use lru::LruCache;
 
 #[derive(Debug, Clone)]
 pub struct Statistics {
@@ -16,20 +17,96 @@ pub struct EngineState {
     pub game_id: String,
     pub current_board: Board,
     pub history: RepetitionHistory,
+    /// Plies since the last capture or pawn move, `Board` does not keep it
+    pub halfmove_clock: u32,
+    /// Hash and halfmove clock of every position from the search root to the current node
+    pub search_path: Vec<(u64, u32)>,
     pub statistics: HashMap<u64, Statistics>,
     pub global_map: Arc<GlobalMap>,
     pub transposition_table: TranspositionTable,
+    pub stop: Arc<AtomicBool>,
+    pub options: EngineOptions,
+    pub ordering: MoveOrdering,
 }
 
+#[derive(Debug, Clone)]
+pub struct EngineOptions {
+    pub null_move_pruning: bool,
+    /// Centipawns the engine gives up to avoid a draw, negative to seek one
+    pub contempt: i32,
+    /// Skill level, those below `MAX_SKILL_LEVEL` weaken play. `MAX_SKILL_LEVEL` itself is
+    /// stored as `None`, full strength.
+    pub skill_level: Option<u8>,
+}
+
+/// Move ordering state learned while searching, kept for the whole game
+#[derive(Debug, Clone)]
+pub struct MoveOrdering {
+    /// Two quiet moves per ply that recently caused a beta cutoff
+    pub killers: Vec<[Option<ChessMove>; 2]>,
+    /// Butterfly table indexed by side to move, source and destination square
+    pub history: Vec<i32>,
+    /// Quiet reply that refuted a move, indexed by that move's source and destination square
+    pub counter_moves: Vec<Option<ChessMove>>,
+}
+
+pub const MAX_PLY: usize = 128;
+
 #[derive(Debug)]
-pub struct GlobalMap {}
+pub struct GlobalMap {
+    /// Search threads used when a request does not ask for a specific count
+    pub threads: usize,
+    /// Time lost per move to the network and the GUI, kept out of every budget
+    pub move_overhead_ms: u128,
+    /// Upper bound on the time spent on a single move, if any
+    pub max_move_time_ms: Option<u128>,
+    /// Contempt of new games that do not set their own
+    pub contempt: i32,
+    /// Transposition table size of new games that do not set their own
+    pub hash_mb: usize,
+    /// Server-wide table games may opt into instead of allocating their own
+    pub shared_table: Option<TranspositionTable>,
+    /// Ceiling on the memory of every transposition table of the server together
+    pub hash_memory_limit_mb: usize,
+    /// Memory held by the `HashReservation` of every table apart from the shared one
+    pub hash_reserved_mb: Arc<AtomicUsize>,
+    /// Directory transposition table snapshots are saved to and loaded from through the API
+    pub snapshot_dir: PathBuf,
+}
 
 #[derive(Clone)]
 pub struct ServerState {
     pub engines: Arc<DashMap<String, EngineState>>,
+    pub jobs: Arc<DashMap<String, SearchJob>>,
+    pub ponders: Arc<DashMap<String, PonderJob>>,
     pub global_map: Arc<GlobalMap>,
 }
 
+/// A search running in the background for a game, polled and stopped through the API
+#[derive(Debug, Clone)]
+pub struct SearchJob {
+    pub game_id: String,
+    pub stop: Arc<AtomicBool>,
+    pub progress: Arc<Mutex<SearchProgress>>,
+}
+
+/// Search on the position after the reply we expect, run while the opponent thinks
+#[derive(Debug, Clone)]
+pub struct PonderJob {
+    pub expected_move: ChessMove,
+    pub board: Board,
+    pub job: SearchJob,
+    /// Set once the opponent actually played `expected_move`
+    pub hit_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchProgress {
+    pub result: SearchResult,
+    /// Set once the search is over and `result` is final
+    pub finished_at: Option<Instant>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum SpecialMove {
     Check,
@@ -46,19 +123,135 @@ pub enum BoundType {
     UpperBound,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct TTEntry {
     pub value: i32,
     pub depth: u8,
     pub flag: BoundType,
     pub best_move: Option<ChessMove>,
 }
+/// One stored entry. `key` is the position hash xor'd with the packed `data`, so an entry
+/// torn by two threads writing at once fails verification instead of being misread.
+#[derive(Debug, Default)]
+pub struct TTSlot {
+    pub key: AtomicU64,
+    pub data: AtomicU64,
+}
+
+/// Slots a position may be stored in, sharing one cache line
+#[derive(Debug, Default)]
+#[repr(align(64))]
+pub struct TTBucket {
+    pub slots: [TTSlot; TT_BUCKET_SLOTS],
+}
+
+/// Fixed-size, lock-free hash table shared by every thread searching a game
 #[derive(Debug, Clone)]
 pub struct TranspositionTable {
-    pub inner: Arc<Mutex<LruCache<(u64, u8), TTEntry>>>,
+    /// Always a power of two long
+    pub buckets: Arc<Vec<TTBucket>>,
+    /// Bumped for every search, entries left by older ones are replaced first
+    pub generation: Arc<AtomicU8>,
+    /// Share of the server's hash memory ceiling, held until the last handle is dropped
+    pub reservation: Option<Arc<HashReservation>>,
 }
 
-pub const TT_TABLE_SIZE: usize = 100_1000;
+/// Memory taken off the server's hash memory ceiling, given back when dropped
+#[derive(Debug)]
+pub struct HashReservation {
+    pub reserved_mb: Arc<AtomicUsize>,
+    pub size_mb: usize,
+}
+
+pub const TT_BUCKET_SLOTS: usize = 4;
+/// Table size of games and searches that do not ask for one
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 4096;
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub time_left_ms: Option<u128>,
+    pub time_limit_ms: Option<u128>,
+    pub opponent_time_ms: Option<u128>,
+    pub increment_ms: u128,
+    pub moves_to_go: Option<u32>,
+    /// Overrides `GlobalMap::move_overhead_ms`
+    pub move_overhead_ms: Option<u128>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Stop as soon as a mate in at most this many moves is found
+    pub mate: Option<u8>,
+    pub infinite: bool,
+    /// Always search, even when the opening book knows the position
+    pub skip_book: bool,
+    /// Side the contempt is applied for, the side to move at the root when `None`
+    pub engine_color: Option<chess::Color>,
+    pub threads: Option<usize>,
+    pub multipv: Option<usize>,
+    /// Allow-list of root moves, all legal moves when `None`
+    pub search_moves: Option<Vec<ChessMove>>,
+    /// Root moves that are never searched
+    pub excluded_moves: Vec<ChessMove>,
+}
+
+/// Soft and hard time limits for one move, the soft one rescaled after every iteration
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    pub start: Instant,
+    /// Time to aim for, scaled by how settled the search looks
+    pub soft_ms: Option<u128>,
+    /// Time the search must never exceed
+    pub hard_ms: Option<u128>,
+    pub scale: f64,
+    pub best_move: Option<ChessMove>,
+    pub stable_iterations: u32,
+    pub previous_eval: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchControl {
+    pub deadline: Option<Instant>,
+    pub node_limit: Option<u64>,
+    pub stop: Arc<AtomicBool>,
+    /// Only these moves are searched at the root, all legal moves when `None`
+    pub root_moves: Option<Vec<ChessMove>>,
+    /// Score of a draw from White's point of view, the contempt pushes it away from the engine
+    pub white_draw_score: i32,
+    /// Searched first at the root, the move the previous iteration chose for this line
+    pub root_best: Option<ChessMove>,
+    /// Helper threads add their nodes here as they go, so reports can include them
+    pub node_sink: Option<Arc<AtomicU64>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PvLine {
+    pub best_move: ChessMove,
+    pub eval: i32,
+    pub depth: u8,
+    pub pv: Vec<ChessMove>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    pub eval: i32,
+    pub nodes: u64,
+    pub time_ms: u128,
+    pub depth: u8,
+    pub seldepth: u8,
+    pub pv: Vec<ChessMove>,
+    /// Every MultiPV line of the last completed iteration, best first
+    pub lines: Vec<PvLine>,
+    /// Permille of the transposition table filled by this search
+    pub hashfull: u32,
+}
+
+/// Evaluation term scored separately for the middlegame and the endgame, from White's view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaperedScore {
+    pub mg: i32,
+    pub eg: i32,
+}
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct OpeningEntry(pub String, pub u32);
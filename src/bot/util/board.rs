@@ -1,5 +1,19 @@
 use std::collections::HashSet;
-use chess::{ Board, ChessMove, MoveGen, Piece };
+use chess::{
+    get_bishop_moves,
+    get_king_moves,
+    get_knight_moves,
+    get_pawn_attacks,
+    get_rook_moves,
+    BitBoard,
+    Board,
+    ChessMove,
+    Color,
+    MoveGen,
+    Piece,
+    Square,
+    EMPTY,
+};
 use crate::bot::{ include::types::SpecialMove, util::piece::piece_value };
 
 pub trait BoardExt {
@@ -9,9 +23,55 @@ pub trait BoardExt {
     fn is_attack(&self, mv: ChessMove) -> bool;
     fn is_quiet_position(&self) -> bool;
     fn move_priority(&self, mv: ChessMove) -> i32;
-    fn halfmove_clock(&self) -> u32;
     fn capture_pieces(&self, mv: ChessMove) -> Option<(Piece, Piece)>;
     fn material_score(&self, color: chess::Color) -> i32;
+    fn static_exchange(&self, mv: ChessMove) -> i32;
+    fn game_phase(&self) -> i32;
+}
+
+/// Game phase with all minor and major pieces on the board, it drops to 0 once only pawns are left
+pub const MAX_PHASE: i32 = 24;
+
+/// Halfmove clock field of a FEN, 0 when it is missing
+pub fn fen_halfmove_clock(fen: &str) -> u32 {
+    fen.split_whitespace()
+        .nth(4)
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(0)
+}
+
+/// Halfmove clock after `mv`, captures and pawn moves reset it
+pub fn next_halfmove_clock(board: &Board, mv: ChessMove, clock: u32) -> u32 {
+    let capture = board.piece_on(mv.get_dest()).is_some();
+    if capture || board.piece_on(mv.get_source()) == Some(Piece::Pawn) {
+        0
+    } else {
+        clock + 1
+    }
+}
+
+// The king is worth more than anything it could win, so it only ever captures last
+fn exchange_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::King => 20_000,
+        _ => piece_value(piece),
+    }
+}
+
+fn attackers_to(board: &Board, sq: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn);
+    let diagonal = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let straight = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+
+    let attackers =
+        get_pawn_attacks(sq, Color::Black, pawns & board.color_combined(Color::White)) |
+        get_pawn_attacks(sq, Color::White, pawns & board.color_combined(Color::Black)) |
+        (get_knight_moves(sq) & board.pieces(Piece::Knight)) |
+        (get_king_moves(sq) & board.pieces(Piece::King)) |
+        (get_bishop_moves(sq, occupied) & diagonal) |
+        (get_rook_moves(sq, occupied) & straight);
+
+    attackers & occupied
 }
 
 pub fn is_noisy(classification: &HashSet<SpecialMove>) -> bool {
@@ -59,18 +119,14 @@ impl BoardExt for Board {
         if self.piece_on(mv.get_dest()).is_some() {
             return false;
         }
-        match self.en_passant() {
-            Some(ep_sq) if ep_sq == mv.get_dest() => true,
-            _ => false,
-        }
+        // The board keeps the square of the pawn that can be taken, not the one moved to
+        let victim_sq = Square::make_square(mv.get_source().get_rank(), mv.get_dest().get_file());
+        matches!(self.en_passant(), Some(ep_sq) if ep_sq == victim_sq)
     }
 
     fn is_attack(&self, mv: ChessMove) -> bool {
         let dest = mv.get_dest();
-        match self.color_on(dest) {
-            Some(color) if color != self.side_to_move() => true,
-            _ => false,
-        }
+        matches!(self.color_on(dest), Some(color) if color != self.side_to_move())
     }
 
     fn is_quiet_position(&self) -> bool {
@@ -127,14 +183,6 @@ impl BoardExt for Board {
         tactical_bonus + capture_value_sum / 10
     }
 
-    fn halfmove_clock(&self) -> u32 {
-        self.to_string()
-            .split_whitespace()
-            .nth(4)
-            .and_then(|s| s.parse::<u32>().ok())
-            .unwrap_or(0)
-    }
-
     fn capture_pieces(&self, mv: ChessMove) -> Option<(Piece, Piece)> {
         if !self.classify_move(mv).contains(&SpecialMove::Capture) {
             return None;
@@ -170,4 +218,131 @@ impl BoardExt for Board {
 
         score
     }
+
+    fn game_phase(&self) -> i32 {
+        let count = |piece: Piece| self.pieces(piece).popcnt() as i32;
+        let phase =
+            count(Piece::Knight) +
+            count(Piece::Bishop) +
+            2 * count(Piece::Rook) +
+            4 * count(Piece::Queen);
+        phase.min(MAX_PHASE)
+    }
+
+    fn static_exchange(&self, mv: ChessMove) -> i32 {
+        let (source, dest) = (mv.get_source(), mv.get_dest());
+        let Some(moved) = self.piece_on(source) else {
+            return 0;
+        };
+
+        let mut occupied = *self.combined() ^ BitBoard::from_square(source);
+        let mut gain = [0; 32];
+        gain[0] = if self.is_en_passant(mv) {
+            let victim_sq = Square::make_square(source.get_rank(), dest.get_file());
+            occupied ^= BitBoard::from_square(victim_sq);
+            piece_value(Piece::Pawn)
+        } else {
+            self.piece_on(dest).map(piece_value).unwrap_or(0)
+        };
+
+        // The piece now standing on `dest`, which the opponent may capture next
+        let mut on_square = match mv.get_promotion() {
+            Some(promoted) => {
+                gain[0] += piece_value(promoted) - piece_value(Piece::Pawn);
+                promoted
+            }
+            None => moved,
+        };
+
+        let mut side = !self.side_to_move();
+        let mut d = 0;
+        while d + 1 < gain.len() {
+            let attackers = attackers_to(self, dest, occupied) & self.color_combined(side);
+            if attackers == EMPTY {
+                break;
+            }
+
+            // Always recapture with the least valuable piece
+            let Some((piece, from)) = [
+                Piece::Pawn,
+                Piece::Knight,
+                Piece::Bishop,
+                Piece::Rook,
+                Piece::Queen,
+                Piece::King,
+            ]
+                .iter()
+                .map(|&piece| (piece, attackers & self.pieces(piece)))
+                .find(|&(_, bb)| bb != EMPTY)
+                .map(|(piece, bb)| (piece, bb.to_square())) else {
+                break;
+            };
+
+            d += 1;
+            gain[d] = exchange_value(on_square) - gain[d - 1];
+
+            occupied ^= BitBoard::from_square(from);
+            on_square = piece;
+            side = !side;
+        }
+
+        while d > 0 {
+            gain[d - 1] = -(-gain[d - 1]).max(gain[d]);
+            d -= 1;
+        }
+
+        gain[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let board = Board::from_str(fen).unwrap();
+        let mv = ChessMove::from_str(uci).unwrap();
+        assert!(board.legal(mv), "{} is not legal in {}", uci, fen);
+        board.static_exchange(mv)
+    }
+
+    #[test]
+    fn undefended_capture_wins_the_piece() {
+        assert_eq!(see("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5"), 320);
+    }
+
+    #[test]
+    fn defended_capture_loses_the_attacker() {
+        assert_eq!(see("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), -800);
+    }
+
+    #[test]
+    fn xray_attacker_joins_the_exchange() {
+        // Rd2xd5 Rxd5 Rxd5 nets the pawn
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        // Without the second rook the capture loses the exchange
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "d2d5"), -400);
+    }
+
+    #[test]
+    fn least_valuable_recapture_first() {
+        // Black recaptures with the pawn, so the bishop cannot win the queen back
+        assert_eq!(see("3qk3/8/4p3/3n4/8/1B6/3R4/4K3 w - - 0 1", "d2d5"), -180);
+    }
+
+    #[test]
+    fn en_passant_and_promotion() {
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert!(!Board::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap().is_en_passant(
+            ChessMove::from_str("e5e6").unwrap()
+        ));
+        assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800);
+    }
+
+    #[test]
+    fn quiet_move_onto_attacked_square() {
+        assert_eq!(see("4k3/8/4p3/8/8/2N5/8/4K3 w - - 0 1", "c3d5"), -320);
+        assert_eq!(see("4k3/8/8/8/8/2N5/8/4K3 w - - 0 1", "c3d5"), 0);
+    }
 }
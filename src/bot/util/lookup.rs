@@ -1,13 +1,22 @@
-use chess::{ Board, ChessMove };
-use lru::LruCache;
+use chess::{ Board, ChessMove, ALL_PIECES, ALL_SQUARES };
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use crate::bot::include::map::OPENING_DB;
-use crate::bot::include::types::{ OpeningEntry, RepetitionHistory, TTEntry, TranspositionTable };
+use crate::bot::include::types::{
+    BoundType,
+    HashReservation,
+    OpeningEntry,
+    RepetitionHistory,
+    TTBucket,
+    TTEntry,
+    TTSlot,
+    TranspositionTable,
+};
+use std::mem::size_of;
 use crate::bot::util::moves::parse_uci_move;
 use std::collections::HashMap;
-use std::num::NonZeroUsize;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{ AtomicU8, Ordering };
+use std::sync::Arc;
 
 pub fn lookup_opening_db(board: &Board) -> Option<ChessMove> {
     let board_hash = board.get_hash();
@@ -23,6 +32,12 @@ pub fn lookup_opening_db(board: &Board) -> Option<ChessMove> {
     None
 }
 
+impl Default for RepetitionHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RepetitionHistory {
     pub fn new() -> Self {
         Self {
@@ -56,23 +71,356 @@ impl RepetitionHistory {
     }
 }
 
+// Packed entry layout, low bits first: value (32), depth (8), bound (2), generation (6), move (16)
+const GENERATION_MASK: u8 = 0x3f;
+// Depth an entry is worth less per search it has not been touched in
+const AGE_PENALTY: i32 = 8;
+// Entries looked at to estimate how full the table is
+const HASHFULL_SAMPLE: usize = 1000;
+
+fn encode_move(mv: Option<ChessMove>) -> u64 {
+    // a1a1 is never a move, so all zeroes is free to mean none
+    mv.map_or(0, |mv| {
+        let promotion = mv.get_promotion().map_or(0, |piece| (piece.to_index() as u64) + 1);
+        (mv.get_source().to_index() as u64) |
+            ((mv.get_dest().to_index() as u64) << 6) |
+            (promotion << 12)
+    })
+}
+
+fn decode_move(bits: u64) -> Option<ChessMove> {
+    if bits == 0 {
+        return None;
+    }
+    let promotion = match (bits >> 12) & 0x7 {
+        0 => None,
+        piece => Some(ALL_PIECES[(piece - 1) as usize]),
+    };
+    Some(
+        ChessMove::new(
+            ALL_SQUARES[(bits & 0x3f) as usize],
+            ALL_SQUARES[((bits >> 6) & 0x3f) as usize],
+            promotion
+        )
+    )
+}
+
+fn pack(entry: &TTEntry, generation: u8) -> u64 {
+    let flag = match entry.flag {
+        BoundType::Exact => 0,
+        BoundType::LowerBound => 1,
+        BoundType::UpperBound => 2,
+    };
+    (entry.value as u32 as u64) |
+        ((entry.depth as u64) << 32) |
+        (flag << 40) |
+        (((generation & GENERATION_MASK) as u64) << 42) |
+        (encode_move(entry.best_move) << 48)
+}
+
+pub fn unpack(data: u64) -> (TTEntry, u8) {
+    let flag = match (data >> 40) & 0x3 {
+        0 => BoundType::Exact,
+        1 => BoundType::LowerBound,
+        _ => BoundType::UpperBound,
+    };
+    let entry = TTEntry {
+        value: data as u32 as i32,
+        depth: (data >> 32) as u8,
+        flag,
+        best_move: decode_move(data >> 48),
+    };
+    (entry, ((data >> 42) as u8) & GENERATION_MASK)
+}
+
+impl TTSlot {
+    pub fn is_empty(key: u64, data: u64) -> bool {
+        key == 0 && data == 0
+    }
+
+    pub fn store(&self, hash: u64, data: u64) {
+        self.key.store(hash ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+// Rounded down to a power of two so the bucket index is a mask of the hash
+fn bucket_count(size_mb: usize) -> usize {
+    let wanted = ((size_mb << 20) / size_of::<TTBucket>()).max(1);
+    1usize << (usize::BITS - 1 - wanted.leading_zeros())
+}
+
 impl TranspositionTable {
-    pub fn new(capacity: usize) -> Self {
-        let size = NonZeroUsize::new(capacity).expect("TT capacity must be > 0");
+    pub fn new(size_mb: usize) -> Self {
         Self {
-            inner: Arc::new(Mutex::new(LruCache::new(size))),
+            buckets: Arc::new(
+                (0..bucket_count(size_mb)).map(|_| TTBucket::default()).collect()
+            ),
+            generation: Arc::new(AtomicU8::new(0)),
+            reservation: None,
+        }
+    }
+
+    /// Table taking up the memory of `reservation`, which is given back with the last handle
+    pub fn reserved(reservation: HashReservation) -> Self {
+        let size_mb = reservation.size_mb;
+        Self {
+            reservation: Some(Arc::new(reservation)),
+            ..Self::new(size_mb)
+        }
+    }
+
+    /// Memory `new(size_mb)` takes, which may be less than asked for
+    pub fn allocated_mb(size_mb: usize) -> usize {
+        (bucket_count(size_mb) * size_of::<TTBucket>()) >> 20
+    }
+
+    fn bucket(&self, hash: u64) -> &TTBucket {
+        &self.buckets[(hash as usize) & (self.buckets.len() - 1)]
+    }
+
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed) & GENERATION_MASK
+    }
+
+    /// True when both handles point at the same entries
+    pub fn is_same(&self, other: &TranspositionTable) -> bool {
+        Arc::ptr_eq(&self.buckets, &other.buckets)
+    }
+
+    /// Memory taken by the table, which may be less than asked for
+    pub fn size_mb(&self) -> usize {
+        (self.buckets.len() * size_of::<TTBucket>()) >> 20
+    }
+
+    /// Table of a new size holding as many of this one's entries as fit
+    pub fn resized(&self, size_mb: usize) -> Self {
+        let table = Self::new(size_mb);
+        table.copy_from(self);
+        table
+    }
+
+    /// Stores as many of `other`'s entries as fit, whatever the size of either table
+    pub fn copy_from(&self, other: &TranspositionTable) {
+        for slot in other.buckets.iter().flat_map(|bucket| &bucket.slots) {
+            let key = slot.key.load(Ordering::Relaxed);
+            let data = slot.data.load(Ordering::Relaxed);
+            if !TTSlot::is_empty(key, data) {
+                self.put(key ^ data, unpack(data).0);
+            }
+        }
+    }
+
+    /// Forgets every entry, searches running meanwhile just see a colder table
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.slots) {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Permille of sampled entries written by the current search, as UCI reports it
+    pub fn hashfull(&self) -> u32 {
+        let generation = self.generation();
+        let slots = self.buckets
+            .iter()
+            .flat_map(|bucket| &bucket.slots)
+            .take(HASHFULL_SAMPLE);
+
+        let mut sampled = 0;
+        let mut used = 0;
+        for slot in slots {
+            let key = slot.key.load(Ordering::Relaxed);
+            let data = slot.data.load(Ordering::Relaxed);
+            sampled += 1;
+            if !TTSlot::is_empty(key, data) && unpack(data).1 == generation {
+                used += 1;
+            }
         }
+        (used * 1000) / sampled.max(1)
     }
 
-    pub fn get(&self, key: &(u64, u8)) -> Option<TTEntry> {
-        self.inner.lock().unwrap().get(key).cloned()
+    /// Ages every entry by one search, called before each new one starts
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn put(&self, key: (u64, u8), entry: TTEntry) {
-        self.inner.lock().unwrap().put(key, entry);
+    pub fn get(&self, hash: u64) -> Option<TTEntry> {
+        self.bucket(hash).slots.iter().find_map(|slot| {
+            let key = slot.key.load(Ordering::Relaxed);
+            let data = slot.data.load(Ordering::Relaxed);
+            // A slot overwritten halfway by another thread fails this check
+            (key ^ data == hash && !TTSlot::is_empty(key, data)).then(|| unpack(data).0)
+        })
     }
 
-    pub fn clone_arc(&self) -> Arc<Mutex<LruCache<(u64, u8), TTEntry>>> {
-        Arc::clone(&self.inner)
+    pub fn put(&self, hash: u64, entry: TTEntry) {
+        let generation = self.generation();
+        let mut victim = &self.bucket(hash).slots[0];
+        let mut victim_worth = i32::MAX;
+
+        for slot in &self.bucket(hash).slots {
+            let key = slot.key.load(Ordering::Relaxed);
+            let data = slot.data.load(Ordering::Relaxed);
+
+            if key ^ data == hash && !TTSlot::is_empty(key, data) {
+                let (old, old_generation) = unpack(data);
+                // Keep a much deeper result of this search over a shallow bound
+                if
+                    entry.flag != BoundType::Exact &&
+                    (entry.depth as i32) + 2 < (old.depth as i32) &&
+                    old_generation == generation
+                {
+                    return;
+                }
+                let entry = TTEntry {
+                    best_move: entry.best_move.or(old.best_move),
+                    ..entry
+                };
+                slot.store(hash, pack(&entry, generation));
+                return;
+            }
+
+            // Empty slots go first, then shallow and stale ones
+            let worth = if TTSlot::is_empty(key, data) {
+                i32::MIN
+            } else {
+                let (old, old_generation) = unpack(data);
+                let age = (generation.wrapping_sub(old_generation) & GENERATION_MASK) as i32;
+                (old.depth as i32) - AGE_PENALTY * age
+            };
+            if worth < victim_worth {
+                victim = slot;
+                victim_worth = worth;
+            }
+        }
+
+        victim.store(hash, pack(&entry, generation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::{ Piece, Square };
+
+    fn entry(value: i32, depth: u8, flag: BoundType, best_move: Option<ChessMove>) -> TTEntry {
+        TTEntry { value, depth, flag, best_move }
+    }
+
+    fn assert_same(a: &TTEntry, b: &TTEntry) {
+        assert_eq!(
+            (a.value, a.depth, a.flag, a.best_move),
+            (b.value, b.depth, b.flag, b.best_move)
+        );
+    }
+
+    // Hashes sharing a bucket of a 1 MB table, they differ only above the index bits
+    fn same_bucket(i: u64) -> u64 {
+        0x1234 | (i << 40)
+    }
+
+    #[test]
+    fn pack_round_trips() {
+        let promotions = [
+            None,
+            Some(Piece::Queen),
+            Some(Piece::Rook),
+            Some(Piece::Bishop),
+            Some(Piece::Knight),
+        ];
+        let values = [0, 1, -1, 250, -250, 31_000, -31_000, i32::MAX, i32::MIN];
+        let flags = [BoundType::Exact, BoundType::LowerBound, BoundType::UpperBound];
+
+        for (i, promotion) in promotions.into_iter().enumerate() {
+            let mv = ChessMove::new(Square::G7, Square::H8, promotion);
+            for (j, &value) in values.iter().enumerate() {
+                let original = entry(value, (i * 40 + j) as u8, flags[j % 3], Some(mv));
+                let generation = ((i * 13 + j) as u8) & GENERATION_MASK;
+                let (unpacked, unpacked_generation) = unpack(pack(&original, generation));
+                assert_same(&unpacked, &original);
+                assert_eq!(unpacked_generation, generation);
+            }
+        }
+
+        let no_move = entry(-5, u8::MAX, BoundType::UpperBound, None);
+        let (unpacked, generation) = unpack(pack(&no_move, GENERATION_MASK));
+        assert_same(&unpacked, &no_move);
+        assert_eq!(generation, GENERATION_MASK);
+    }
+
+    #[test]
+    fn get_after_put() {
+        let table = TranspositionTable::new(1);
+        let underpromotion = ChessMove::new(Square::A7, Square::A8, Some(Piece::Knight));
+        let stored = entry(-31_000, 7, BoundType::Exact, Some(underpromotion));
+        assert!(table.get(42).is_none());
+        table.put(42, stored);
+        assert_same(&table.get(42).unwrap(), &stored);
+        assert!(table.get(43).is_none());
+
+        // A new bound without a move keeps the one already known
+        table.put(42, entry(12, 9, BoundType::LowerBound, None));
+        assert_eq!(table.get(42).unwrap().best_move, stored.best_move);
+
+        table.clear();
+        assert!(table.get(42).is_none());
+    }
+
+    #[test]
+    fn shallow_bound_keeps_deeper_entry() {
+        let table = TranspositionTable::new(1);
+        table.put(7, entry(100, 12, BoundType::Exact, None));
+        table.put(7, entry(-100, 3, BoundType::UpperBound, None));
+        assert_eq!(table.get(7).unwrap().depth, 12);
+
+        // Exact results always replace
+        table.put(7, entry(50, 3, BoundType::Exact, None));
+        assert_eq!(table.get(7).unwrap().depth, 3);
+    }
+
+    #[test]
+    fn replaces_shallowest_slot() {
+        let table = TranspositionTable::new(1);
+        for (i, depth) in [10, 2, 10, 10].into_iter().enumerate() {
+            table.put(same_bucket(i as u64), entry(0, depth, BoundType::Exact, None));
+        }
+        table.put(same_bucket(4), entry(0, 5, BoundType::Exact, None));
+
+        assert!(table.get(same_bucket(1)).is_none());
+        for i in [0, 2, 3, 4] {
+            assert!(table.get(same_bucket(i)).is_some());
+        }
+    }
+
+    #[test]
+    fn replaces_stale_slot() {
+        let table = TranspositionTable::new(1);
+        table.put(same_bucket(0), entry(0, 12, BoundType::Exact, None));
+        table.new_search();
+        table.new_search();
+        for i in 1..4 {
+            table.put(same_bucket(i), entry(0, 5, BoundType::Exact, None));
+        }
+        table.put(same_bucket(4), entry(0, 5, BoundType::Exact, None));
+
+        // Two searches old, the deep entry is worth less than the fresh shallow ones
+        assert!(table.get(same_bucket(0)).is_none());
+        for i in 1..5 {
+            assert!(table.get(same_bucket(i)).is_some());
+        }
+    }
+
+    #[test]
+    fn resized_keeps_entries() {
+        let table = TranspositionTable::new(1);
+        for hash in 1..200 {
+            table.put(hash, entry(hash as i32, 4, BoundType::Exact, None));
+        }
+        let resized = table.resized(2);
+        assert_eq!(resized.size_mb(), 2);
+        for hash in 1..200 {
+            assert_eq!(resized.get(hash).unwrap().value, hash as i32);
+        }
     }
 }
@@ -2,3 +2,6 @@ pub mod lookup;
 pub mod moves;
 pub mod piece;
 pub mod board;
+pub mod state;
+pub mod score;
+pub mod snapshot;
@@ -26,3 +26,12 @@ pub fn parse_uci_move(uci: &str, board: &Board) -> Option<ChessMove> {
     let legal = MoveGen::new_legal(board);
     legal.into_iter().find(|m| *m == candidate)
 }
+
+/// Parses a list of UCI moves, failing on the first one that is not legal in `board`
+pub fn parse_uci_moves(ucis: &[String], board: &Board) -> Result<Vec<ChessMove>, String> {
+    ucis.iter()
+        .map(|uci| {
+            parse_uci_move(uci, board).ok_or_else(|| format!("Illegal or invalid move '{}'", uci))
+        })
+        .collect()
+}
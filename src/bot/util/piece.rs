@@ -10,3 +10,15 @@ pub fn piece_value(piece: Piece) -> i32 {
         Piece::King => 0,
     }
 }
+
+/// Material in the endgame, where pawns and rooks gain and minor pieces lose a little
+pub fn piece_value_end(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 120,
+        Piece::Knight => 300,
+        Piece::Bishop => 320,
+        Piece::Rook => 520,
+        Piece::Queen => 920,
+        Piece::King => 0,
+    }
+}
@@ -0,0 +1,146 @@
+use std::ops::{ Add, AddAssign, Neg, Sub, SubAssign };
+use crate::bot::include::types::TaperedScore;
+use crate::bot::util::board::MAX_PHASE;
+
+// Being mated at the root scores `-MATE_SCORE`, every ply of distance brings it one closer to 0
+pub const MATE_SCORE: i32 = 1_000_000;
+// Anything beyond this is a forced mate rather than an evaluation
+pub const MATE_BOUND: i32 = MATE_SCORE - 1_000;
+// Returned by a search that was stopped, out of reach of every real score in either sign
+pub const ABORTED_SCORE: i32 = MATE_SCORE + 1;
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/// True for the score of a stopped search, after any number of negations
+pub fn is_aborted(score: i32) -> bool {
+    score.abs() == ABORTED_SCORE
+}
+
+/// Score for the side to move when it is checkmated `ply` plies from the root
+pub fn mated_in(ply: u8) -> i32 {
+    -MATE_SCORE + (ply as i32)
+}
+
+/// Mate scores are stored relative to the node, so they stay right when reached at another ply
+pub fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score + (ply as i32)
+    } else if score <= -MATE_BOUND {
+        score - (ply as i32)
+    } else {
+        score
+    }
+}
+
+/// Inverse of `score_to_tt`, turns a stored mate score back into a distance from the root
+pub fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score - (ply as i32)
+    } else if score <= -MATE_BOUND {
+        score + (ply as i32)
+    } else {
+        score
+    }
+}
+
+/// Full moves to mate for a root score, positive when the side to move gives mate
+pub fn mate_in_moves(score: i32) -> Option<i32> {
+    if score >= MATE_BOUND {
+        Some((MATE_SCORE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        Some(-(MATE_SCORE + score) / 2)
+    } else {
+        None
+    }
+}
+
+impl TaperedScore {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    /// Blends both scores, `phase` going from `MAX_PHASE` on a full board down to 0
+    pub fn taper(self, phase: i32) -> i32 {
+        let phase = phase.clamp(0, MAX_PHASE);
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for TaperedScore {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for TaperedScore {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.mg, -self.eg)
+    }
+}
+
+impl AddAssign for TaperedScore {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for TaperedScore {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_scores_count_full_moves() {
+        // Mate delivered on ply 1, 3 and 5 of the line
+        assert_eq!(mate_in_moves(-mated_in(1)), Some(1));
+        assert_eq!(mate_in_moves(-mated_in(3)), Some(2));
+        assert_eq!(mate_in_moves(-mated_in(5)), Some(3));
+        // Mated on ply 2 and 4
+        assert_eq!(mate_in_moves(mated_in(2)), Some(-1));
+        assert_eq!(mate_in_moves(mated_in(4)), Some(-2));
+        assert_eq!(mate_in_moves(MATE_BOUND - 1), None);
+        assert_eq!(mate_in_moves(-350), None);
+    }
+
+    #[test]
+    fn mate_scores_survive_the_table_at_any_ply() {
+        // Found 3 plies below a node at ply 4, read back at ply 9 through a transposition
+        let stored = score_to_tt(-mated_in(7), 4);
+        assert_eq!(stored, -mated_in(3));
+        assert_eq!(score_from_tt(stored, 9), -mated_in(12));
+        assert_eq!(score_from_tt(score_to_tt(mated_in(6), 2), 2), mated_in(6));
+        // Plain evaluations are stored as they are
+        assert_eq!(score_to_tt(420, 10), 420);
+        assert_eq!(score_from_tt(-420, 10), -420);
+    }
+
+    #[test]
+    fn taper_blends_from_middlegame_to_endgame() {
+        let score = TaperedScore::new(100, -40);
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), -40);
+        assert_eq!(score.taper(MAX_PHASE / 2), 30);
+        // Promotions can push the phase past a full board
+        assert_eq!(score.taper(MAX_PHASE + 8), 100);
+        assert_eq!(-score + score, TaperedScore::default());
+    }
+}
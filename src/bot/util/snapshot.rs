@@ -0,0 +1,246 @@
+use std::fs::{ self, File };
+use std::io::{ self, BufReader, BufWriter, Read, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::Ordering;
+use crate::bot::include::types::{ GlobalMap, TTSlot, TranspositionTable, TT_BUCKET_SLOTS };
+use crate::bot::util::lookup::unpack;
+
+// File layout, little endian: magic, version, bucket count, slots per bucket, generation,
+// then the key and data of every slot
+const SNAPSHOT_MAGIC: &[u8; 8] = b"MYSTICTT";
+const SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_EXTENSION: &str = "tt";
+// Magic, version, bucket count, slots per bucket and generation
+const HEADER_BYTES: u64 = 8 + 4 + 8 + 4 + 1;
+// Key and data of a slot
+const SLOT_BYTES: u64 = 16;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// File of the snapshot called `name` in the server's snapshot directory. Only plain names
+/// are taken, so a request can never reach outside of it.
+pub fn snapshot_path(global_map: &GlobalMap, name: &str) -> Result<PathBuf, String> {
+    let valid =
+        !name.is_empty() &&
+        name.len() <= 64 &&
+        !name.starts_with('.') &&
+        name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!("Invalid snapshot name '{}'", name));
+    }
+    Ok(global_map.snapshot_dir.join(format!("{}.{}", name, SNAPSHOT_EXTENSION)))
+}
+
+impl TranspositionTable {
+    /// Writes every slot to `path`, returns the number of entries saved
+    pub fn save(&self, path: &Path) -> io::Result<usize> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written next to the target first, so a crash never leaves half a snapshot behind
+        let partial = path.with_extension("partial");
+        let mut writer = BufWriter::new(File::create(&partial)?);
+
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.buckets.len() as u64).to_le_bytes())?;
+        writer.write_all(&(TT_BUCKET_SLOTS as u32).to_le_bytes())?;
+        writer.write_all(&[self.generation.load(Ordering::Relaxed)])?;
+
+        let mut saved = 0;
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.slots) {
+            let key = slot.key.load(Ordering::Relaxed);
+            let data = slot.data.load(Ordering::Relaxed);
+            if !TTSlot::is_empty(key, data) {
+                saved += 1;
+            }
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(&data.to_le_bytes())?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&partial, path)?;
+        Ok(saved)
+    }
+
+    /// Merges the entries saved in `path` into the table, returns how many were loaded.
+    /// A snapshot of another size is rehashed entry by entry.
+    pub fn load(&self, path: &Path) -> io::Result<usize> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(invalid("not a transposition table snapshot"));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(invalid(&format!("unsupported snapshot version {}", version)));
+        }
+        let buckets = read_u64(&mut reader)?;
+        if read_u32(&mut reader)? as usize != TT_BUCKET_SLOTS {
+            return Err(invalid("snapshot has a different bucket layout"));
+        }
+        // Checked against the file before anything is written, so a corrupt or truncated
+        // snapshot leaves the table as it was
+        let slots = buckets
+            .checked_mul(TT_BUCKET_SLOTS as u64)
+            .filter(|_| buckets.is_power_of_two());
+        let expected_len = slots
+            .and_then(|slots| slots.checked_mul(SLOT_BYTES))
+            .and_then(|bytes| bytes.checked_add(HEADER_BYTES));
+        let (Some(slots), Some(expected_len)) = (slots, expected_len) else {
+            return Err(invalid("snapshot header is corrupt"));
+        };
+        if expected_len != file_len {
+            return Err(invalid("snapshot is truncated or has trailing data"));
+        }
+        let buckets = buckets as usize;
+        let mut generation = [0; 1];
+        reader.read_exact(&mut generation)?;
+
+        let same_size = buckets == self.buckets.len();
+        if same_size {
+            self.generation.store(generation[0], Ordering::Relaxed);
+        }
+
+        let mut loaded = 0;
+        for index in 0..slots as usize {
+            let key = read_u64(&mut reader)?;
+            let data = read_u64(&mut reader)?;
+            if TTSlot::is_empty(key, data) {
+                continue;
+            }
+            if same_size {
+                self.buckets[index / TT_BUCKET_SLOTS].slots[index % TT_BUCKET_SLOTS].store(
+                    key ^ data,
+                    data
+                );
+            } else {
+                self.put(key ^ data, unpack(data).0);
+            }
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::{ ChessMove, Square };
+    use crate::bot::include::types::{ BoundType, TTEntry };
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mystic-bot-{}-{}.tt", name, std::process::id()))
+    }
+
+    fn entry(value: i32, depth: u8) -> TTEntry {
+        TTEntry {
+            value,
+            depth,
+            flag: BoundType::LowerBound,
+            best_move: Some(ChessMove::new(Square::E2, Square::E4, None)),
+        }
+    }
+
+    fn filled_table() -> TranspositionTable {
+        let table = TranspositionTable::new(1);
+        for hash in 1..=500u64 {
+            table.put(hash.wrapping_mul(0x9e37_79b9_7f4a_7c15), entry(hash as i32, (hash % 50) as u8));
+        }
+        table
+    }
+
+    fn assert_holds_entries(table: &TranspositionTable) {
+        for hash in 1..=500u64 {
+            let stored = table.get(hash.wrapping_mul(0x9e37_79b9_7f4a_7c15)).expect("entry lost");
+            assert_eq!(stored.value, hash as i32);
+            assert_eq!(stored.depth, (hash % 50) as u8);
+            assert_eq!(stored.flag, BoundType::LowerBound);
+            assert_eq!(stored.best_move, entry(0, 0).best_move);
+        }
+    }
+
+    #[test]
+    fn round_trip_same_size() {
+        let path = temp_path("same-size");
+        let saved = filled_table().save(&path).unwrap();
+        assert_eq!(saved, 500);
+
+        let loaded = TranspositionTable::new(1);
+        assert_eq!(loaded.load(&path).unwrap(), 500);
+        assert_holds_entries(&loaded);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn round_trip_other_size() {
+        let path = temp_path("other-size");
+        filled_table().save(&path).unwrap();
+
+        let loaded = TranspositionTable::new(2);
+        assert_eq!(loaded.load(&path).unwrap(), 500);
+        assert_holds_entries(&loaded);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupt_header_and_truncation() {
+        let path = temp_path("corrupt");
+        filled_table().save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        // A bucket count that overflows the slot count
+        let mut corrupt = bytes.clone();
+        corrupt[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&path, &corrupt).unwrap();
+        let table = TranspositionTable::new(1);
+        assert!(table.load(&path).is_err());
+
+        // Cut off halfway, nothing may reach the table
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(table.load(&path).is_err());
+        assert!(table.get(0x9e37_79b9_7f4a_7c15).is_none());
+
+        fs::write(&path, b"not a snapshot").unwrap();
+        assert!(table.load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_names_stay_in_the_directory() {
+        let global_map = GlobalMap {
+            threads: 1,
+            move_overhead_ms: 0,
+            max_move_time_ms: None,
+            contempt: 0,
+            hash_mb: 1,
+            shared_table: None,
+            hash_memory_limit_mb: 1,
+            hash_reserved_mb: Default::default(),
+            snapshot_dir: PathBuf::from("snapshots"),
+        };
+        assert_eq!(snapshot_path(&global_map, "deep-run_1").unwrap(), PathBuf::from("snapshots/deep-run_1.tt"));
+        for name in ["", "../etc", "a/b", ".hidden", "c:\\x"] {
+            assert!(snapshot_path(&global_map, name).is_err(), "{} accepted", name);
+        }
+    }
+}
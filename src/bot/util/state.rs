@@ -0,0 +1,349 @@
+use chess::{ Board, ChessMove, MoveGen };
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{ atomic::{ AtomicBool, AtomicUsize, Ordering }, Arc };
+use std::time::Instant;
+use crate::bot::include::types::{
+    EngineOptions,
+    EngineState,
+    GlobalMap,
+    HashReservation,
+    MoveOrdering,
+    RepetitionHistory,
+    SearchControl,
+    SearchLimits,
+    TranspositionTable,
+    DEFAULT_HASH_MB,
+};
+use crate::bot::util::{ board::next_halfmove_clock, moves::parse_uci_moves };
+
+impl EngineState {
+    pub fn new(
+        game_id: String,
+        current_board: Board,
+        history: RepetitionHistory,
+        global_map: Arc<GlobalMap>,
+        transposition_table: TranspositionTable
+    ) -> Self {
+        let options = EngineOptions {
+            contempt: global_map.contempt,
+            ..EngineOptions::default()
+        };
+        Self {
+            game_id,
+            current_board,
+            history,
+            halfmove_clock: 0,
+            search_path: Vec::new(),
+            statistics: HashMap::new(),
+            global_map,
+            transposition_table,
+            stop: Arc::new(AtomicBool::new(false)),
+            options,
+            ordering: MoveOrdering::new(),
+        }
+    }
+
+    /// Plays `mv` on the game's current position
+    pub fn play_move(&mut self, mv: ChessMove) {
+        self.halfmove_clock = next_halfmove_clock(&self.current_board, mv, self.halfmove_clock);
+        self.current_board = self.current_board.make_move_new(mv);
+        self.history.increment(self.current_board.get_hash());
+    }
+
+    /// Halfmove clock of the node being searched
+    pub fn search_halfmove_clock(&self) -> u32 {
+        self.search_path.last().map_or(self.halfmove_clock, |&(_, clock)| clock)
+    }
+
+    /// Steps the search into the position after `mv`, undone by `leave_position`
+    pub fn enter_position(&mut self, board: &Board, mv: ChessMove) -> Board {
+        let new_board = board.make_move_new(mv);
+        let hash = new_board.get_hash();
+        let clock = next_halfmove_clock(board, mv, self.search_halfmove_clock());
+        self.history.increment(hash);
+        self.search_path.push((hash, clock));
+        new_board
+    }
+
+    pub fn leave_position(&mut self) {
+        if let Some((hash, _)) = self.search_path.pop() {
+            self.history.decrement(hash);
+        }
+    }
+
+    /// True when the game searches with the server-wide table
+    pub fn uses_shared_table(&self) -> bool {
+        self.global_map.shared_table
+            .as_ref()
+            .is_some_and(|shared| shared.is_same(&self.transposition_table))
+    }
+
+    /// True when the current node already occurred between the search root and itself
+    pub fn is_path_repetition(&self) -> bool {
+        let Some((&(hash, clock), earlier)) = self.search_path.split_last() else {
+            return false;
+        };
+        // Only positions with the same side to move, and no irreversible move in between
+        earlier
+            .iter()
+            .rev()
+            .take(clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&(previous, _)| previous == hash)
+    }
+}
+
+impl GlobalMap {
+    /// Memory taken by every transposition table, including the shared one and the
+    /// temporary tables of running evaluations
+    pub fn hash_memory_mb(&self) -> usize {
+        let shared = self.shared_table.as_ref().map_or(0, |table| table.size_mb());
+        shared + self.hash_reserved_mb.load(Ordering::SeqCst)
+    }
+
+    /// Memory a new table may still take before the server's ceiling is reached
+    pub fn free_hash_memory_mb(&self) -> usize {
+        self.hash_memory_limit_mb.saturating_sub(self.hash_memory_mb())
+    }
+
+    /// Takes `size_mb` off the ceiling in one step, so concurrent requests can never
+    /// overshoot it together. None when it does not fit.
+    pub fn reserve_hash(&self, size_mb: usize) -> Option<HashReservation> {
+        let shared = self.shared_table.as_ref().map_or(0, |table| table.size_mb());
+        let limit = self.hash_memory_limit_mb.saturating_sub(shared);
+        self.hash_reserved_mb
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |reserved| {
+                reserved.checked_add(size_mb).filter(|&total| total <= limit)
+            })
+            .ok()
+            .map(|_| HashReservation {
+                reserved_mb: Arc::clone(&self.hash_reserved_mb),
+                size_mb,
+            })
+    }
+
+    /// Private table of at most `size_mb`, None when the ceiling leaves no room for it
+    pub fn reserve_table(&self, size_mb: usize) -> Option<TranspositionTable> {
+        let reservation = self.reserve_hash(TranspositionTable::allocated_mb(size_mb))?;
+        Some(TranspositionTable::reserved(reservation))
+    }
+}
+
+impl Drop for HashReservation {
+    fn drop(&mut self) {
+        self.reserved_mb.fetch_sub(self.size_mb, Ordering::SeqCst);
+    }
+}
+
+impl Default for GlobalMap {
+    /// Settings of a server started without flags, apart from the shared table it leaves out
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            move_overhead_ms: 30,
+            max_move_time_ms: None,
+            contempt: 0,
+            hash_mb: DEFAULT_HASH_MB,
+            shared_table: None,
+            hash_memory_limit_mb: 1024,
+            hash_reserved_mb: Arc::new(AtomicUsize::new(0)),
+            snapshot_dir: PathBuf::from("snapshots"),
+        }
+    }
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            null_move_pruning: true,
+            contempt: 0,
+            skill_level: None,
+        }
+    }
+}
+
+// Nodes a helper counts before passing them on, so threads do not fight over the counter
+const NODE_BATCH: u64 = 1024;
+
+impl SearchControl {
+    /// Counts a visited node
+    pub fn count_node(&self, nodes: &mut u64) {
+        *nodes += 1;
+        if let Some(sink) = &self.node_sink {
+            if nodes.is_multiple_of(NODE_BATCH) {
+                sink.fetch_add(NODE_BATCH, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Passes on what a finished helper counted since its last batch
+    pub fn flush_nodes(&self, nodes: u64) {
+        if let Some(sink) = &self.node_sink {
+            sink.fetch_add(nodes % NODE_BATCH, Ordering::Relaxed);
+        }
+    }
+
+    /// Draw score for the side to move, `color` being 1 for White and -1 for Black
+    pub fn draw_score(&self, color: i32) -> i32 {
+        self.white_draw_score * color
+    }
+
+    /// True once the search has to unwind: stopped externally, out of time or out of nodes
+    pub fn should_stop(&self, nodes: u64) -> bool {
+        self.stop.load(Ordering::Relaxed) ||
+            self.deadline.is_some_and(|deadline| Instant::now() >= deadline) ||
+            self.node_limit.is_some_and(|limit| nodes >= limit)
+    }
+}
+
+impl SearchLimits {
+    /// Sets the root allow-list and deny-list from UCI moves, validated against `board`
+    pub fn restrict_root(
+        &mut self,
+        board: &Board,
+        search_moves: Option<&[String]>,
+        excluded_moves: Option<&[String]>
+    ) -> Result<(), String> {
+        self.search_moves = search_moves.map(|moves| parse_uci_moves(moves, board)).transpose()?;
+        self.excluded_moves = parse_uci_moves(excluded_moves.unwrap_or_default(), board)?;
+        Ok(())
+    }
+
+    /// False when nothing but an external stop would end the search
+    pub fn is_bounded(&self) -> bool {
+        !self.infinite &&
+            (self.time_left_ms.is_some() ||
+                self.time_limit_ms.is_some() ||
+                self.depth.is_some() ||
+                self.nodes.is_some() ||
+                self.mate.is_some())
+    }
+
+    /// True when a ponder search, one unrestricted line searched until stopped, can stand in
+    /// for a search with these limits. The depth it reached is checked on the result.
+    pub fn accepts_ponder_result(&self) -> bool {
+        self.nodes.is_none() &&
+            self.mate.is_none() &&
+            self.multipv.unwrap_or(1) <= 1 &&
+            !self.restricts_root()
+    }
+
+    pub fn restricts_root(&self) -> bool {
+        self.search_moves.is_some() || !self.excluded_moves.is_empty()
+    }
+
+    /// The root moves left after applying both lists, `None` when unrestricted
+    pub fn root_moves(&self, board: &Board) -> Option<Vec<ChessMove>> {
+        if !self.restricts_root() {
+            return None;
+        }
+
+        Some(
+            MoveGen::new_legal(board)
+                .filter(|mv| self.search_moves.as_ref().is_none_or(|allowed| allowed.contains(mv)))
+                .filter(|mv| !self.excluded_moves.contains(mv))
+                .collect()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn global_map(limit_mb: usize, shared_mb: Option<usize>) -> GlobalMap {
+        GlobalMap {
+            threads: 1,
+            move_overhead_ms: 0,
+            max_move_time_ms: None,
+            contempt: 0,
+            hash_mb: 1,
+            shared_table: shared_mb.map(TranspositionTable::new),
+            hash_memory_limit_mb: limit_mb,
+            hash_reserved_mb: Default::default(),
+            snapshot_dir: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn reservations_stay_under_the_ceiling() {
+        let global_map = global_map(8, Some(2));
+        let first = global_map.reserve_table(4).unwrap();
+        assert_eq!(global_map.hash_memory_mb(), 6);
+        assert!(global_map.reserve_table(4).is_none());
+        assert_eq!(global_map.free_hash_memory_mb(), 2);
+
+        // Rounded down before reserving, 3 MB only takes 2
+        let second = global_map.reserve_table(3).unwrap();
+        assert_eq!(second.size_mb(), 2);
+        assert_eq!(global_map.free_hash_memory_mb(), 0);
+        drop(second);
+
+        // Handles share the reservation, it is given back with the last one
+        let handle = first.clone();
+        drop(first);
+        assert_eq!(global_map.hash_memory_mb(), 6);
+        drop(handle);
+        assert_eq!(global_map.hash_memory_mb(), 2);
+    }
+
+    #[test]
+    fn concurrent_reservations_never_overshoot() {
+        let global_map = Arc::new(global_map(10, None));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let global_map = Arc::clone(&global_map);
+                std::thread::spawn(move || global_map.reserve_hash(3))
+            })
+            .collect();
+        let granted: Vec<_> = threads
+            .into_iter()
+            .filter_map(|thread| thread.join().unwrap())
+            .collect();
+        assert_eq!(granted.len(), 3);
+        assert_eq!(global_map.hash_memory_mb(), 9);
+        drop(granted);
+        assert_eq!(global_map.hash_memory_mb(), 0);
+    }
+
+    #[test]
+    fn ponder_results_only_stand_in_for_plain_searches() {
+        let plain = SearchLimits { time_left_ms: Some(60_000), ..Default::default() };
+        assert!(plain.accepts_ponder_result());
+        assert!(SearchLimits { depth: Some(12), ..plain.clone() }.accepts_ponder_result());
+        assert!(SearchLimits { multipv: Some(1), ..plain.clone() }.accepts_ponder_result());
+
+        assert!(!SearchLimits { nodes: Some(10_000), ..plain.clone() }.accepts_ponder_result());
+        assert!(!SearchLimits { mate: Some(3), ..plain.clone() }.accepts_ponder_result());
+        assert!(!SearchLimits { multipv: Some(2), ..plain.clone() }.accepts_ponder_result());
+
+        let board = Board::default();
+        let mut restricted = plain.clone();
+        restricted.restrict_root(&board, None, Some(&["e2e4".to_string()])).unwrap();
+        assert!(!restricted.accepts_ponder_result());
+        restricted.restrict_root(&board, Some(&["e2e4".to_string()]), None).unwrap();
+        assert!(!restricted.accepts_ponder_result());
+    }
+
+    #[test]
+    fn root_restrictions_must_be_legal() {
+        let board = Board::default();
+        let mut limits = SearchLimits::default();
+        let moves = |ucis: &[&str]| ucis.iter().map(|uci| uci.to_string()).collect::<Vec<_>>();
+
+        let allowed = moves(&["e2e4", "d2d4"]);
+        limits.restrict_root(&board, Some(&allowed), Some(&moves(&["d2d4"]))).unwrap();
+        assert_eq!(limits.root_moves(&board).unwrap().len(), 1);
+
+        let err = limits.restrict_root(&board, Some(&moves(&["e2e5"])), None).unwrap_err();
+        assert_eq!(err, "Illegal or invalid move 'e2e5'");
+        assert!(limits.restrict_root(&board, None, Some(&moves(&["nonsense"]))).is_err());
+
+        // Excluding every allowed move leaves nothing to search
+        limits.restrict_root(&board, Some(&moves(&["e2e4"])), Some(&moves(&["e2e4"]))).unwrap();
+        assert_eq!(limits.root_moves(&board), Some(vec![]));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod api;
-pub mod bot;
\ No newline at end of file
+pub mod bot;
+pub mod uci;
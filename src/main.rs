@@ -2,21 +2,33 @@ use axum::{ http::Response, routing::{ get, post, delete }, Router };
 use dashmap::DashMap;
 use mystic_bot::{
     api::{
-        delete::delete_game::delete_game_handler,
+        delete::{ clear_hash::clear_hash_handler, delete_game::delete_game_handler },
         get::{
             get_eval::eval_position_handler,
+            memory::memory_handler,
             root::root_handler,
+            search_job::search_progress_handler,
             static_eval::static_eval_handler,
         },
         post::{
             add_game::new_game_handler,
             best_move::best_move_handler,
+            hash_snapshot::{ load_hash_handler, save_hash_handler },
             make_move::make_move_handler,
+            resize_hash::resize_hash_handler,
+            search_job::{ start_search_handler, stop_search_handler },
         },
     },
-    bot::include::types::{ GlobalMap, ServerState },
+    bot::include::types::{
+        GlobalMap,
+        ServerState,
+        TranspositionTable,
+        DEFAULT_HASH_MB,
+        MAX_HASH_MB,
+    },
+    uci::session::UciSession,
 };
-use std::{ net::SocketAddr, sync::Arc, time::Duration };
+use std::{ net::SocketAddr, path::PathBuf, sync::{ atomic::AtomicUsize, Arc }, time::Duration };
 use tower_http::trace::{ TraceLayer, DefaultMakeSpan, DefaultOnRequest };
 use tracing::Span;
 use tracing_subscriber::{ fmt, layer::SubscriberExt, util::SubscriberInitExt };
@@ -30,10 +42,99 @@ struct Cli {
     /// Port number to bind the server
     #[arg(short, long, default_value_t = 8080)]
     port: u16,
+
+    /// Default number of search threads per request
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Milliseconds lost per move to the network and the GUI, kept out of every time budget
+    #[arg(long, default_value_t = 30)]
+    move_overhead_ms: u128,
+
+    /// Never think longer than this many milliseconds on a single move
+    #[arg(long)]
+    max_move_time_ms: Option<u128>,
+
+    /// Centipawns the engine gives up to avoid a draw, negative to play for one
+    #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+    contempt: i32,
+
+    /// Transposition table size in MB of every game that does not set its own
+    #[arg(long, default_value_t = DEFAULT_HASH_MB)]
+    hash_mb: usize,
+
+    /// Size in MB of the table games can share instead of owning one, 0 to disable it
+    #[arg(long, default_value_t = DEFAULT_HASH_MB)]
+    shared_hash_mb: usize,
+
+    /// Ceiling in MB on the memory of all transposition tables together
+    #[arg(long, default_value_t = 1024)]
+    hash_memory_limit_mb: usize,
+
+    /// Directory the API saves and loads transposition table snapshots in
+    #[arg(long, default_value = "snapshots")]
+    snapshot_dir: PathBuf,
+
+    /// Snapshot to load at startup, into the shared hash or the UCI session's
+    #[arg(long)]
+    load_hash: Option<PathBuf>,
+
+    /// Speak the UCI protocol on stdin/stdout instead of serving the API
+    #[arg(long)]
+    uci: bool,
+}
+
+fn main() {
+    // Parse CLI args
+    let cli = Cli::parse();
+
+    let global_map = Arc::new(GlobalMap {
+        threads: cli.threads,
+        move_overhead_ms: cli.move_overhead_ms,
+        max_move_time_ms: cli.max_move_time_ms,
+        contempt: cli.contempt,
+        hash_mb: cli.hash_mb.clamp(1, MAX_HASH_MB),
+        // Only the API serves several games at once
+        shared_table: (!cli.uci && cli.shared_hash_mb > 0).then(|| {
+            TranspositionTable::new(cli.shared_hash_mb.min(MAX_HASH_MB).min(cli.hash_memory_limit_mb))
+        }),
+        hash_memory_limit_mb: cli.hash_memory_limit_mb,
+        hash_reserved_mb: Arc::new(AtomicUsize::new(0)),
+        snapshot_dir: cli.snapshot_dir.clone(),
+    });
+
+    if cli.uci {
+        let mut session = UciSession::new(Arc::clone(&global_map));
+        if let Some(path) = &cli.load_hash {
+            // Printed as an info string, anything else would confuse the GUI
+            match session.load_hash(path) {
+                Ok(entries) => println!("info string loaded {} hash entries", entries),
+                Err(err) => println!("info string could not load hash: {}", err),
+            }
+        }
+        session.run();
+        return;
+    }
+
+    if let Some(path) = &cli.load_hash {
+        let Some(shared) = &global_map.shared_table else {
+            eprintln!("--load-hash needs the shared hash, it is disabled by --shared-hash-mb 0");
+            std::process::exit(1);
+        };
+        match shared.load(path) {
+            Ok(entries) => println!("Loaded {} entries into the shared hash", entries),
+            Err(err) => {
+                eprintln!("Could not load hash snapshot {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    serve(cli, global_map);
 }
 
 #[tokio::main]
-async fn main() {
+async fn serve(cli: Cli, global_map: Arc<GlobalMap>) {
     let logo =
         r#"
  .--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--..--. 
@@ -74,19 +175,14 @@ async fn main() {
 
     println!("{}", logo);
 
-    // Parse CLI args
-    let cli = Cli::parse();
-
     // Set up tracing subscriber for logging
     tracing_subscriber::registry().with(fmt::layer()).init();
 
     // Create shared state
-    let global_map = Arc::new(GlobalMap {
-        // init fields here if needed
-    });
-
     let state = ServerState {
         engines: Arc::new(DashMap::new()),
+        jobs: Arc::new(DashMap::new()),
+        ponders: Arc::new(DashMap::new()),
         global_map,
     };
 
@@ -103,8 +199,16 @@ async fn main() {
         .route("/game", delete(delete_game_handler))
         .route("/game/best", post(best_move_handler))
         .route("/game/move", post(make_move_handler))
+        .route("/game/hash", post(resize_hash_handler))
+        .route("/game/hash", delete(clear_hash_handler))
+        .route("/game/hash/save", post(save_hash_handler))
+        .route("/game/hash/load", post(load_hash_handler))
+        .route("/game/search", post(start_search_handler))
+        .route("/game/search/:job_id", get(search_progress_handler))
+        .route("/game/search/:job_id/stop", post(stop_search_handler))
         .route("/eval", get(eval_position_handler))
         .route("/static", get(static_eval_handler))
+        .route("/memory", get(memory_handler))
         .layer(trace_layer)
         .with_state(state);
 
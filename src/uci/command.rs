@@ -0,0 +1,255 @@
+/// Parameters of a UCI `go` command, times are in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub wtime: Option<u128>,
+    pub btime: Option<u128>,
+    pub winc: Option<u128>,
+    pub binc: Option<u128>,
+    pub movestogo: Option<u32>,
+    pub movetime: Option<u128>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub mate: Option<u8>,
+    pub infinite: bool,
+    pub searchmoves: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UciCommand {
+    Uci,
+    IsReady,
+    UciNewGame,
+    Position {
+        fen: Option<String>,
+        moves: Vec<String>,
+    },
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    Go(GoParams),
+    Stop,
+    Quit,
+    Unknown(String),
+}
+
+fn parse_position(tokens: &[&str]) -> UciCommand {
+    let moves_at = tokens.iter().position(|&t| t == "moves");
+    let (setup, moves) = match moves_at {
+        Some(i) => (&tokens[..i], &tokens[i + 1..]),
+        None => (tokens, &[][..]),
+    };
+
+    let fen = match setup.first() {
+        Some(&"startpos") => None,
+        Some(&"fen") => Some(setup[1..].join(" ")),
+        _ => {
+            return UciCommand::Unknown(format!("position {}", tokens.join(" ")));
+        }
+    };
+
+    UciCommand::Position {
+        fen,
+        moves: moves
+            .iter()
+            .map(|m| m.to_string())
+            .collect(),
+    }
+}
+
+fn parse_setoption(tokens: &[&str]) -> UciCommand {
+    // Option names may contain spaces: `setoption name Move Overhead value 30`
+    let value_at = tokens.iter().position(|&t| t == "value");
+    let name_end = value_at.unwrap_or(tokens.len());
+    let name = match tokens.first() {
+        Some(&"name") => tokens[1..name_end].join(" "),
+        _ => {
+            return UciCommand::Unknown(format!("setoption {}", tokens.join(" ")));
+        }
+    };
+    let value = value_at.map(|i| tokens[i + 1..].join(" "));
+
+    UciCommand::SetOption { name, value }
+}
+
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
+fn parse_go(tokens: &[&str]) -> GoParams {
+    let mut params = GoParams::default();
+    let mut iter = tokens.iter().peekable();
+
+    while let Some(&token) = iter.next() {
+        // `searchmoves` takes every following token up to the next keyword
+        if token == "searchmoves" {
+            while let Some(&&mv) = iter.peek() {
+                if GO_KEYWORDS.contains(&mv) {
+                    break;
+                }
+                params.searchmoves.push(mv.to_string());
+                iter.next();
+            }
+            continue;
+        }
+
+        // Every other keyword except `infinite` is followed by a numeric value
+        let mut value = || iter.next().and_then(|v| v.parse::<u128>().ok());
+        match token {
+            "wtime" => {
+                params.wtime = value();
+            }
+            "btime" => {
+                params.btime = value();
+            }
+            "winc" => {
+                params.winc = value();
+            }
+            "binc" => {
+                params.binc = value();
+            }
+            "movestogo" => {
+                params.movestogo = value().map(|m| m.min(u32::MAX as u128) as u32);
+            }
+            "movetime" => {
+                params.movetime = value();
+            }
+            "depth" => {
+                params.depth = value().map(|d| d.min(u8::MAX as u128) as u8);
+            }
+            "nodes" => {
+                params.nodes = value().map(|n| n.min(u64::MAX as u128) as u64);
+            }
+            "mate" => {
+                params.mate = value().map(|m| m.min(u8::MAX as u128) as u8);
+            }
+            "infinite" => {
+                params.infinite = true;
+            }
+            _ => {}
+        }
+    }
+
+    params
+}
+
+pub fn parse_command(line: &str) -> UciCommand {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    match tokens.first() {
+        Some(&"uci") => UciCommand::Uci,
+        Some(&"isready") => UciCommand::IsReady,
+        Some(&"ucinewgame") => UciCommand::UciNewGame,
+        Some(&"setoption") => parse_setoption(&tokens[1..]),
+        Some(&"position") => parse_position(&tokens[1..]),
+        Some(&"go") => UciCommand::Go(parse_go(&tokens[1..])),
+        Some(&"stop") => UciCommand::Stop,
+        Some(&"quit") => UciCommand::Quit,
+        _ => UciCommand::Unknown(line.trim().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_commands() {
+        assert_eq!(parse_command("uci"), UciCommand::Uci);
+        assert_eq!(parse_command("  isready \n"), UciCommand::IsReady);
+        assert_eq!(parse_command("ucinewgame"), UciCommand::UciNewGame);
+        assert_eq!(parse_command("stop"), UciCommand::Stop);
+        assert_eq!(parse_command("quit"), UciCommand::Quit);
+        assert_eq!(parse_command(" debug on "), UciCommand::Unknown("debug on".to_string()));
+        assert_eq!(parse_command(""), UciCommand::Unknown(String::new()));
+    }
+
+    #[test]
+    fn position() {
+        assert_eq!(parse_command("position startpos"), UciCommand::Position {
+            fen: None,
+            moves: vec![],
+        });
+        assert_eq!(parse_command("position startpos moves e2e4 e7e5"), UciCommand::Position {
+            fen: None,
+            moves: vec!["e2e4".to_string(), "e7e5".to_string()],
+        });
+        assert_eq!(
+            parse_command("position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1"),
+            UciCommand::Position {
+                fen: Some("4k3/8/8/8/8/8/8/4K2R w K - 0 1".to_string()),
+                moves: vec!["e1g1".to_string()],
+            }
+        );
+        assert_eq!(
+            parse_command("position somewhere"),
+            UciCommand::Unknown("position somewhere".to_string())
+        );
+    }
+
+    #[test]
+    fn setoption() {
+        assert_eq!(parse_command("setoption name Move Overhead value 30"), UciCommand::SetOption {
+            name: "Move Overhead".to_string(),
+            value: Some("30".to_string()),
+        });
+        assert_eq!(parse_command("setoption name Clear Hash"), UciCommand::SetOption {
+            name: "Clear Hash".to_string(),
+            value: None,
+        });
+        assert_eq!(
+            parse_command("setoption Hash 64"),
+            UciCommand::Unknown("setoption Hash 64".to_string())
+        );
+    }
+
+    #[test]
+    fn go() {
+        let UciCommand::Go(params) = parse_command(
+            "go wtime 60000 btime 55000 winc 1000 binc 900 movestogo 20"
+        ) else {
+            panic!("not a go command");
+        };
+        assert_eq!(params, GoParams {
+            wtime: Some(60000),
+            btime: Some(55000),
+            winc: Some(1000),
+            binc: Some(900),
+            movestogo: Some(20),
+            ..GoParams::default()
+        });
+
+        let UciCommand::Go(params) = parse_command(
+            "go searchmoves e2e4 d2d4 depth 300 nodes 5000 mate 3 movetime 100 infinite"
+        ) else {
+            panic!("not a go command");
+        };
+        assert_eq!(params, GoParams {
+            searchmoves: vec!["e2e4".to_string(), "d2d4".to_string()],
+            depth: Some(u8::MAX),
+            nodes: Some(5000),
+            mate: Some(3),
+            movetime: Some(100),
+            infinite: true,
+            ..GoParams::default()
+        });
+
+        // A value that is not a number is dropped, the rest still parses
+        assert_eq!(parse_command("go depth x movetime 50"), UciCommand::Go(GoParams {
+            movetime: Some(50),
+            ..GoParams::default()
+        }));
+        assert_eq!(parse_command("go"), UciCommand::Go(GoParams::default()));
+    }
+}
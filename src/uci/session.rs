@@ -0,0 +1,382 @@
+use chess::{ Board, ChessMove, Color };
+use std::io::{ self, BufRead };
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{ atomic::{ AtomicBool, Ordering }, Arc };
+use std::thread::{ self, JoinHandle };
+use std::time::Duration;
+use crate::bot::{
+    algorithm::{ root::search, skill::MAX_SKILL_LEVEL },
+    include::types::{
+        EngineState,
+        GlobalMap,
+        RepetitionHistory,
+        SearchLimits,
+        SearchResult,
+        TranspositionTable,
+        MAX_HASH_MB,
+    },
+    util::{ board::fen_halfmove_clock, moves::parse_uci_move, score::mate_in_moves },
+};
+use crate::uci::command::{ parse_command, GoParams, UciCommand };
+
+pub struct UciSession {
+    global_map: Arc<GlobalMap>,
+    engine: Option<EngineState>,
+    search_thread: Option<JoinHandle<EngineState>>,
+    stop: Arc<AtomicBool>,
+    threads: Option<usize>,
+    multipv: Option<usize>,
+    own_book: bool,
+    move_overhead_ms: Option<u128>,
+}
+
+fn new_engine(global_map: Arc<GlobalMap>, transposition_table: TranspositionTable) -> EngineState {
+    let board = Board::default();
+    let mut history = RepetitionHistory::new();
+    history.increment(board.get_hash());
+
+    EngineState::new("uci".to_string(), board, history, global_map, transposition_table)
+}
+
+fn uci_score(eval: i32) -> String {
+    match mate_in_moves(eval) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", eval),
+    }
+}
+
+fn print_info(result: &SearchResult) {
+    let nps = ((result.nodes as u128) * 1000) / result.time_ms.max(1);
+    for (index, line) in result.lines.iter().enumerate() {
+        let pv = line.pv
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "info depth {} seldepth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+            line.depth,
+            result.seldepth,
+            index + 1,
+            uci_score(line.eval),
+            result.nodes,
+            nps,
+            result.hashfull,
+            result.time_ms,
+            pv
+        );
+    }
+}
+
+fn limits_for(params: &GoParams, side: Color) -> SearchLimits {
+    let (time_left_ms, opponent_time_ms, increment_ms) = match side {
+        Color::White => (params.wtime, params.btime, params.winc),
+        Color::Black => (params.btime, params.wtime, params.binc),
+    };
+
+    SearchLimits {
+        time_left_ms,
+        time_limit_ms: params.movetime,
+        opponent_time_ms,
+        increment_ms: increment_ms.unwrap_or(0),
+        moves_to_go: params.movestogo,
+        depth: params.depth,
+        nodes: params.nodes,
+        mate: params.mate,
+        infinite: params.infinite,
+        ..Default::default()
+    }
+}
+
+impl UciSession {
+    pub fn new(global_map: Arc<GlobalMap>) -> Self {
+        let transposition_table = TranspositionTable::new(global_map.hash_mb);
+        let engine = new_engine(Arc::clone(&global_map), transposition_table);
+        let stop = Arc::clone(&engine.stop);
+        Self {
+            global_map,
+            engine: Some(engine),
+            search_thread: None,
+            stop,
+            threads: None,
+            multipv: None,
+            own_book: true,
+            move_overhead_ms: None,
+        }
+    }
+
+    /// Merges a transposition table snapshot into the session's table
+    pub fn load_hash(&mut self, path: &Path) -> io::Result<usize> {
+        self.idle_engine().transposition_table.load(path)
+    }
+
+    /// Reads commands from stdin until `quit` or EOF
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !self.handle(parse_command(&line)) {
+                break;
+            }
+        }
+        self.stop_search();
+    }
+
+    /// Handles a single command, returns false once the session should end
+    pub fn handle(&mut self, command: UciCommand) -> bool {
+        match command {
+            UciCommand::Uci => {
+                println!("id name MysticBot");
+                println!("id author shashankp28");
+                // Answered from the server settings, a running search goes on undisturbed
+                println!(
+                    "option name Threads type spin default {} min 1 max 256",
+                    self.global_map.threads
+                );
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    self.global_map.hash_mb,
+                    MAX_HASH_MB
+                );
+                println!("option name Clear Hash type button");
+                println!("option name MultiPV type spin default 1 min 1 max 64");
+                println!("option name NullMove type check default true");
+                println!("option name OwnBook type check default true");
+                println!("option name Skill Level type spin default 20 min 0 max 20");
+                println!(
+                    "option name Contempt type spin default {} min -1000 max 1000",
+                    self.global_map.contempt
+                );
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    self.global_map.move_overhead_ms
+                );
+                println!("uciok");
+            }
+            UciCommand::IsReady => {
+                println!("readyok");
+            }
+            UciCommand::UciNewGame => {
+                let engine = self.idle_engine();
+                // The table keeps the size set through the Hash option
+                engine.transposition_table.clear();
+                let fresh = new_engine(
+                    Arc::clone(&engine.global_map),
+                    engine.transposition_table.clone()
+                );
+                *engine = EngineState {
+                    stop: Arc::clone(&engine.stop),
+                    options: engine.options.clone(),
+                    ..fresh
+                };
+            }
+            UciCommand::SetOption { name, value } => self.set_option(&name, value.as_deref()),
+            UciCommand::Position { fen, moves } => self.set_position(fen, &moves),
+            UciCommand::Go(params) => self.go(params),
+            UciCommand::Stop => self.stop_search(),
+            UciCommand::Quit => {
+                return false;
+            }
+            UciCommand::Unknown(line) => {
+                if !line.is_empty() {
+                    println!("info string unknown command: {}", line);
+                }
+            }
+        }
+        true
+    }
+
+    /// Engine state of the session, a running search is stopped first so it can be handed back
+    fn idle_engine(&mut self) -> &mut EngineState {
+        self.stop_search();
+        self.engine.as_mut().expect("engine state is owned by the session between searches")
+    }
+
+    /// The only place the search thread is joined, `stop` is always set before so that
+    /// `go infinite` cannot keep it waiting
+    fn stop_search(&mut self) {
+        if let Some(handle) = self.search_thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.engine = Some(handle.join().expect("UCI search thread panicked"));
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) {
+        let engine = self.idle_engine();
+        match (name.to_lowercase().as_str(), value) {
+            ("threads", Some(value)) => {
+                match value.parse::<usize>() {
+                    Ok(threads) => {
+                        self.threads = Some(threads.clamp(1, 256));
+                    }
+                    Err(_) => println!("info string invalid Threads value: {}", value),
+                }
+            }
+            ("multipv", Some(value)) => {
+                match value.parse::<usize>() {
+                    Ok(multipv) => {
+                        self.multipv = Some(multipv.clamp(1, 64));
+                    }
+                    Err(_) => println!("info string invalid MultiPV value: {}", value),
+                }
+            }
+            ("nullmove", Some(value)) => {
+                engine.options.null_move_pruning = value.eq_ignore_ascii_case("true");
+            }
+            ("move overhead", Some(value)) => {
+                match value.parse::<u128>() {
+                    Ok(overhead) => {
+                        self.move_overhead_ms = Some(overhead.min(5000));
+                    }
+                    Err(_) => println!("info string invalid Move Overhead value: {}", value),
+                }
+            }
+            ("contempt", Some(value)) => {
+                match value.parse::<i32>() {
+                    Ok(contempt) => {
+                        engine.options.contempt = contempt.clamp(-1000, 1000);
+                    }
+                    Err(_) => println!("info string invalid Contempt value: {}", value),
+                }
+            }
+            ("skill level", Some(value)) => {
+                match value.parse::<u8>() {
+                    Ok(skill) => {
+                        engine.options.skill_level = Some(skill).filter(
+                            |&skill| skill < MAX_SKILL_LEVEL
+                        );
+                    }
+                    Err(_) => println!("info string invalid Skill Level value: {}", value),
+                }
+            }
+            ("hash", Some(value)) => {
+                match value.parse::<usize>() {
+                    // GUIs set the option on start, which must not drop a loaded snapshot
+                    Ok(hash_mb) => {
+                        engine.transposition_table = engine.transposition_table.resized(
+                            hash_mb.clamp(1, MAX_HASH_MB)
+                        );
+                    }
+                    Err(_) => println!("info string invalid Hash value: {}", value),
+                }
+            }
+            ("clear hash", _) => engine.transposition_table.clear(),
+            ("ownbook", Some(value)) => {
+                self.own_book = value.eq_ignore_ascii_case("true");
+            }
+            _ => println!("info string unknown option: {}", name),
+        }
+    }
+
+    fn set_position(&mut self, fen: Option<String>, moves: &[String]) {
+        let engine = self.idle_engine();
+
+        let (board, halfmove_clock) = match fen {
+            None => (Board::default(), 0),
+            Some(fen) =>
+                match Board::from_str(&fen) {
+                    Ok(board) => (board, fen_halfmove_clock(&fen)),
+                    Err(_) => {
+                        println!("info string invalid fen: {}", fen);
+                        return;
+                    }
+                }
+        };
+
+        engine.current_board = board;
+        engine.halfmove_clock = halfmove_clock;
+        engine.history = RepetitionHistory::new();
+        engine.history.increment(board.get_hash());
+        for uci in moves {
+            let Some(mv) = parse_uci_move(uci, &engine.current_board) else {
+                println!("info string illegal move: {}", uci);
+                break;
+            };
+            engine.play_move(mv);
+        }
+    }
+
+    fn go(&mut self, params: GoParams) {
+        self.stop_search();
+        let Some(mut engine) = self.engine.take() else {
+            return;
+        };
+
+        let mut limits = limits_for(&params, engine.current_board.side_to_move());
+        limits.threads = self.threads;
+        limits.multipv = self.multipv;
+        limits.skip_book = !self.own_book;
+        limits.move_overhead_ms = self.move_overhead_ms;
+        // Illegal `searchmoves` are ignored, and none left means no restriction
+        let search_moves: Vec<ChessMove> = params.searchmoves
+            .iter()
+            .filter_map(|uci| parse_uci_move(uci, &engine.current_board))
+            .collect();
+        if !search_moves.is_empty() {
+            limits.search_moves = Some(search_moves);
+        }
+        let stop = Arc::clone(&self.stop);
+        stop.store(false, Ordering::Relaxed);
+
+        self.search_thread = Some(
+            thread::spawn(move || {
+                let board = engine.current_board;
+                let result = search(&limits, &board, &mut engine, Some(&print_info));
+                // Iteration reports may lag behind the helpers, the last line has every node
+                print_info(&result);
+
+                // `go infinite` must not report a move before the GUI sends `stop`
+                while limits.infinite && !stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+
+                match result.best_move {
+                    Some(mv) => println!("bestmove {}", mv),
+                    None => println!("bestmove 0000"),
+                }
+                engine
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn commands_during_infinite_search_stop_it() {
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            let mut session = UciSession::new(Arc::new(GlobalMap::default()));
+            session.handle(parse_command("go infinite"));
+            thread::sleep(Duration::from_millis(50));
+
+            // `uci` is answered without touching the search
+            session.handle(parse_command("uci"));
+            assert!(session.search_thread.is_some());
+
+            session.handle(parse_command("position startpos moves e2e4"));
+            assert!(session.search_thread.is_none());
+            session.handle(parse_command("stop"));
+            done.send(session.engine.map(|engine| engine.current_board)).unwrap();
+        });
+
+        let board = finished.recv_timeout(Duration::from_secs(30)).expect("session hung");
+        let e2e4 = ChessMove::from_str("e2e4").unwrap();
+        assert_eq!(board, Some(Board::default().make_move_new(e2e4)));
+    }
+
+    #[test]
+    fn stop_ends_infinite_search() {
+        let mut session = UciSession::new(Arc::new(GlobalMap::default()));
+        session.handle(parse_command("go infinite"));
+        session.handle(parse_command("stop"));
+        assert!(session.search_thread.is_none());
+        assert!(session.engine.is_some());
+    }
+}